@@ -1,6 +1,7 @@
 use aoc_runner_derive::aoc_lib;
 
 mod error;
-mod solutions;
+mod rng;
+pub mod solutions;
 
 aoc_lib! {year = 2021}
@@ -1,102 +1,129 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-#[aoc_generator(day3)]
-pub fn generator(input: &str) -> (Vec<usize>, usize) {
-    let width = input.lines().map(|line| line.len()).max().unwrap();
-    let input: Vec<usize> = input
-        .lines()
-        .map(|line| usize::from_str_radix(line, 2).unwrap())
-        .collect();
-
-    (input, width)
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub numbers: Vec<usize>,
+    pub width: usize,
+    pub radix: usize,
 }
 
-fn nth_bit(num: &usize, n: usize, width: usize) -> bool {
-    num & (1 << (width - n - 1)) != 0
-}
+impl Report {
+    pub fn new(input: &str, radix: u32) -> Self {
+        assert!(
+            (2..=36).contains(&radix),
+            "radix must be in 2..=36, got {}",
+            radix
+        );
+
+        let width = input.lines().map(|line| line.len()).max().unwrap();
+        let numbers: Vec<usize> = input
+            .lines()
+            .map(|line| usize::from_str_radix(line, radix).unwrap())
+            .collect();
+
+        Self {
+            numbers,
+            width,
+            radix: radix as usize,
+        }
+    }
 
-fn set_nth_bit(num: &mut usize, n: usize, width: usize) {
-    *num |= 1 << (width - n - 1)
-}
+    fn place_value(&self, n: usize) -> usize {
+        self.radix.pow((self.width - n - 1) as u32)
+    }
 
-fn most_common_nth_bit(nums: &Vec<usize>, n: usize, width: usize) -> bool {
-    let zeros = nums.iter().filter(|num| !nth_bit(num, n, width)).count();
-    let ones = nums.iter().filter(|num| nth_bit(num, n, width)).count();
+    fn nth_digit(&self, num: &usize, n: usize) -> usize {
+        (num / self.place_value(n)) % self.radix
+    }
 
-    ones >= zeros
-}
+    fn set_nth_digit(&self, num: &mut usize, n: usize, digit: usize) {
+        *num += digit * self.place_value(n)
+    }
 
-#[aoc(day3, part1)]
-pub fn solver_1(input: &(Vec<usize>, usize)) -> usize {
-    let (input, width) = input;
+    fn nth_digit_counts(&self, nums: &[usize], n: usize) -> Vec<usize> {
+        let mut counts = vec![0; self.radix];
+        nums.iter()
+            .for_each(|num| counts[self.nth_digit(num, n)] += 1);
 
-    let (mut gamma, mut epsilon) = (0usize, 0usize);
-    for n in 0..*width {
-        let mncb = most_common_nth_bit(input, n, *width);
-        if mncb {
-            set_nth_bit(&mut gamma, n, *width);
-        } else {
-            set_nth_bit(&mut epsilon, n, *width);
-        }
+        counts
     }
+}
 
-    gamma * epsilon
+// ties go to the largest digit, so for binary a tie picks 1
+fn most_common(counts: &[usize]) -> usize {
+    counts
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, count)| **count)
+        .map(|(digit, _)| digit)
+        .unwrap()
 }
 
-#[aoc(day3, part2)]
-pub fn solver_2(input: &(Vec<usize>, usize)) -> usize {
-    let (input, width) = input;
+// Only the digits that actually occur count, otherwise any radix above 2 would make epsilon the
+// smallest missing digit and the CO2 filter would throw away every remaining number. Ties go to the
+// smallest digit, so for binary a tie picks 0.
+fn least_common(counts: &[usize]) -> usize {
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .min_by_key(|(_, count)| **count)
+        .map(|(digit, _)| digit)
+        .unwrap()
+}
 
-    let mut oxygen_generator = input.clone();
-    for n in 0..*width {
-        let mcnb = most_common_nth_bit(&oxygen_generator, n, *width);
-        oxygen_generator.retain(|num| nth_bit(num, n, *width) == mcnb);
+fn rating(report: &Report, criteria: fn(&[usize]) -> usize) -> usize {
+    let mut remaining = report.numbers.clone();
+    for n in 0..report.width {
+        let digit = criteria(&report.nth_digit_counts(&remaining, n));
+        remaining.retain(|num| report.nth_digit(num, n) == digit);
 
-        if oxygen_generator.len() == 1 {
+        if remaining.len() == 1 {
             break;
         }
     }
-    let oxygen_generator = oxygen_generator[0];
 
-    let mut co2_scrubber = input.clone();
-    for n in 0..*width {
-        let mcnb = most_common_nth_bit(&co2_scrubber, n, *width);
-        co2_scrubber.retain(|num| nth_bit(num, n, *width) != mcnb);
+    remaining[0]
+}
 
-        if co2_scrubber.len() == 1 {
-            break;
-        }
+pub fn power_consumption(report: &Report) -> usize {
+    let (mut gamma, mut epsilon) = (0usize, 0usize);
+    for n in 0..report.width {
+        let counts = report.nth_digit_counts(&report.numbers, n);
+        report.set_nth_digit(&mut gamma, n, most_common(&counts));
+        report.set_nth_digit(&mut epsilon, n, least_common(&counts));
     }
-    let co2_scrubber = co2_scrubber[0];
+
+    gamma * epsilon
+}
+
+pub fn life_support_rating(report: &Report) -> usize {
+    let oxygen_generator = rating(report, most_common);
+    let co2_scrubber = rating(report, least_common);
 
     oxygen_generator * co2_scrubber
 }
 
+#[aoc_generator(day3)]
+pub fn generator(input: &str) -> Report {
+    Report::new(input, 2)
+}
+
+#[aoc(day3, part1)]
+pub fn solver_1(input: &Report) -> usize {
+    power_consumption(input)
+}
+
+#[aoc(day3, part2)]
+pub fn solver_2(input: &Report) -> usize {
+    life_support_rating(input)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
-    #[test]
-    fn test_nth_bit() {
-        let width = 8;
-        assert!(nth_bit(&0b0001_0000, 3, width));
-        assert!(nth_bit(&0b0100_0000, 1, width));
-        assert!(nth_bit(&0b0000_0010, 6, width));
-        assert!(nth_bit(&0b0000_1000, 4, width));
-    }
-
-    #[test]
-    fn test_set_nth_bit() {
-        let mut num = 0;
-        let width = 4;
-        set_nth_bit(&mut num, 0, width);
-        set_nth_bit(&mut num, 2, width);
-        assert_eq!(num, 0b1010);
-    }
-
-    #[test]
-    fn test_example_2() {
-        let example = r"00100
+    const INPUT: &str = r"00100
 11110
 10110
 10111
@@ -108,8 +135,63 @@ mod test {
 11001
 00010
 01010";
-        let result = solver_2(&generator(example));
+
+    #[test]
+    fn test_nth_digit() {
+        let report = Report::new("00000000", 2);
+        assert_eq!(report.nth_digit(&0b0001_0000, 3), 1);
+        assert_eq!(report.nth_digit(&0b0100_0000, 1), 1);
+        assert_eq!(report.nth_digit(&0b0000_0010, 6), 1);
+        assert_eq!(report.nth_digit(&0b0000_1000, 4), 1);
+
+        let report = Report::new("000", 16);
+        assert_eq!(report.nth_digit(&0xa3f, 0), 0xa);
+        assert_eq!(report.nth_digit(&0xa3f, 1), 0x3);
+        assert_eq!(report.nth_digit(&0xa3f, 2), 0xf);
+    }
+
+    #[test]
+    fn test_set_nth_digit() {
+        let report = Report::new("0000", 2);
+        let mut num = 0;
+        report.set_nth_digit(&mut num, 0, 1);
+        report.set_nth_digit(&mut num, 2, 1);
+        assert_eq!(num, 0b1010);
+
+        let report = Report::new("000", 3);
+        let mut num = 0;
+        report.set_nth_digit(&mut num, 0, 2);
+        report.set_nth_digit(&mut num, 2, 1);
+        assert_eq!(num, 19);
+    }
+
+    #[test]
+    fn test_example_1() {
+        let result = solver_1(&generator(INPUT));
+
+        assert_eq!(result, 198);
+    }
+
+    #[test]
+    fn test_example_2() {
+        let result = solver_2(&generator(INPUT));
 
         assert_eq!(result, 230);
     }
+
+    #[test]
+    fn test_ternary() {
+        let report = Report::new("210\n100\n010\n121\n211\n212\n122\n120", 3);
+
+        assert_eq!(power_consumption(&report), 12);
+        assert_eq!(life_support_rating(&report), 51);
+    }
+
+    #[test]
+    fn test_hex() {
+        let report = Report::new("1f\n2a\na0\naf\n2f", 16);
+
+        assert_eq!(power_consumption(&report), 2800);
+        assert_eq!(life_support_rating(&report), 5425);
+    }
 }
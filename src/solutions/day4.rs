@@ -1,50 +1,183 @@
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, fmt, thread};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::rng::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WinRule {
+    Row,
+    Column,
+    // either diagonal, only on square boards
+    Diagonal,
+    FourCorners,
+    FullCard,
+}
+
+pub const CLASSIC_RULES: &[WinRule] = &[WinRule::Row, WinRule::Column];
+
 #[derive(Clone)]
 pub struct Board {
     pub numbers: HashMap<usize, (usize, usize)>,
-    pub mask: [[bool; 5]; 5],
+    pub mask: Vec<Vec<bool>>,
+    // hit counters kept up to date by `mark`, so wins can be detected without rescanning
+    row_hits: Vec<usize>,
+    col_hits: Vec<usize>,
+    col_lens: Vec<usize>,
+    diagonal_hits: [usize; 2],
+    corners: Vec<(usize, usize)>,
+    corner_hits: usize,
+    hits: usize,
+    square: bool,
 }
 
 impl Board {
     pub fn new(board: &str) -> Self {
         let mut numbers: HashMap<usize, (usize, usize)> = HashMap::new();
-        board.lines().enumerate().for_each(|(row, line)| {
-            line.split_whitespace().enumerate().for_each(|(col, num)| {
-                numbers.insert(num.parse().unwrap(), (row, col));
-            })
-        });
-        let mask = [[false; 5]; 5];
+        let mut mask: Vec<Vec<bool>> = Vec::new();
+        board
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .for_each(|(row, line)| {
+                mask.push(Vec::new());
+                line.split_whitespace().enumerate().for_each(|(col, num)| {
+                    numbers.insert(num.parse().unwrap(), (row, col));
+                    mask[row].push(false);
+                })
+            });
+
+        let cols = mask.iter().map(|row| row.len()).max().unwrap_or(0);
+        let col_lens = (0..cols)
+            .map(|col| mask.iter().filter(|row| row.len() > col).count())
+            .collect();
+        let square = mask.iter().all(|row| row.len() == mask.len());
+
+        let mut corners: Vec<(usize, usize)> = match (mask.first(), mask.last()) {
+            (Some(top), Some(bottom)) if !top.is_empty() && !bottom.is_empty() => {
+                let last = mask.len() - 1;
+                vec![
+                    (0, 0),
+                    (0, top.len() - 1),
+                    (last, 0),
+                    (last, bottom.len() - 1),
+                ]
+            }
+            _ => Vec::new(),
+        };
+        corners.sort_unstable();
+        corners.dedup();
+
+        Self {
+            numbers,
+            row_hits: vec![0; mask.len()],
+            col_hits: vec![0; cols],
+            col_lens,
+            diagonal_hits: [0, 0],
+            corners,
+            corner_hits: 0,
+            hits: 0,
+            square,
+            mask,
+        }
+    }
 
-        Self { numbers, mask }
+    pub fn rows(&self) -> usize {
+        self.mask.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.col_lens.len()
     }
 
     pub fn mark(&mut self, number: &usize) -> bool {
         match self.numbers.get(number) {
             Some((row, col)) => {
-                self.mask[*row][*col] = true;
+                let (row, col) = (*row, *col);
+                self.mark_at(row, col);
                 true
             }
             None => false,
         }
     }
 
+    // returns false if the cell was already marked
+    fn mark_at(&mut self, row: usize, col: usize) -> bool {
+        if self.mask[row][col] {
+            return false;
+        }
+
+        self.mask[row][col] = true;
+        self.row_hits[row] += 1;
+        self.col_hits[col] += 1;
+        if self.square && row == col {
+            self.diagonal_hits[0] += 1;
+        }
+        if self.square && row + col == self.rows() - 1 {
+            self.diagonal_hits[1] += 1;
+        }
+        if self.corners.contains(&(row, col)) {
+            self.corner_hits += 1;
+        }
+        self.hits += 1;
+
+        true
+    }
+
     pub fn check(&self) -> bool {
-        for row in 0..5 {
-            if self.mask[row].into_iter().all(|m| m) {
-                return true;
-            }
+        self.check_rules(CLASSIC_RULES)
+    }
+
+    pub fn check_rules(&self, rules: &[WinRule]) -> bool {
+        rules.iter().any(|rule| self.check_rule(rule))
+    }
+
+    fn check_rule(&self, rule: &WinRule) -> bool {
+        match rule {
+            WinRule::Row => (0..self.rows()).any(|row| self.row_complete(row)),
+            WinRule::Column => (0..self.cols()).any(|col| self.col_complete(col)),
+            WinRule::Diagonal => (0..2).any(|diagonal| self.diagonal_complete(diagonal)),
+            WinRule::FourCorners => self.corners_complete(),
+            WinRule::FullCard => self.hits == self.numbers.len(),
         }
+    }
 
-        for col in 0..5 {
-            if (0..5).into_iter().map(|row| self.mask[row][col]).all(|m| m) {
-                return true;
+    // like `check_rules`, but only looks at the lines passing through the given cell, which is
+    // enough to tell whether marking that cell won the board
+    fn check_rules_at(&self, row: usize, col: usize, rules: &[WinRule]) -> bool {
+        rules.iter().any(|rule| match rule {
+            WinRule::Row => self.row_complete(row),
+            WinRule::Column => self.col_complete(col),
+            WinRule::Diagonal => {
+                (row == col && self.diagonal_complete(0))
+                    || (row + col + 1 == self.rows() && self.diagonal_complete(1))
             }
-        }
+            WinRule::FourCorners => self.corners.contains(&(row, col)) && self.corners_complete(),
+            WinRule::FullCard => self.hits == self.numbers.len(),
+        })
+    }
+
+    fn row_complete(&self, row: usize) -> bool {
+        self.row_hits[row] > 0 && self.row_hits[row] == self.mask[row].len()
+    }
+
+    // On irregular boards only the columns every row reaches count, a column that a few short
+    // rows stop before would otherwise be won with just a number or two.
+    fn col_full(&self, col: usize) -> bool {
+        self.rows() > 0 && self.col_lens[col] == self.rows()
+    }
+
+    fn col_complete(&self, col: usize) -> bool {
+        self.col_full(col) && self.col_hits[col] == self.rows()
+    }
+
+    // diagonals only count on square boards
+    fn diagonal_complete(&self, diagonal: usize) -> bool {
+        self.square && self.rows() > 0 && self.diagonal_hits[diagonal] == self.rows()
+    }
 
-        false
+    fn corners_complete(&self) -> bool {
+        !self.corners.is_empty() && self.corner_hits == self.corners.len()
     }
 
     pub fn calculate_score(&self) -> usize {
@@ -54,11 +187,51 @@ impl Board {
             .map(|(number, _)| number)
             .sum::<usize>()
     }
+
+    // every set of numbers which, once all drawn, wins the board under the given rules
+    pub fn winning_sets(&self, rules: &[WinRule]) -> Vec<Vec<usize>> {
+        let mut grid: Vec<Vec<usize>> = self.mask.iter().map(|row| vec![0; row.len()]).collect();
+        self.numbers
+            .iter()
+            .for_each(|(number, (row, col))| grid[*row][*col] = *number);
+
+        let n = self.rows();
+        let mut sets: Vec<Vec<usize>> = Vec::new();
+        for rule in rules {
+            match rule {
+                WinRule::Row => sets.extend(grid.iter().filter(|row| !row.is_empty()).cloned()),
+                WinRule::Column => sets.extend(
+                    (0..self.cols())
+                        .filter(|col| self.col_full(*col))
+                        .map(|col| grid.iter().map(|row| row[col]).collect()),
+                ),
+                WinRule::Diagonal if self.square && n > 0 => {
+                    sets.push((0..n).map(|i| grid[i][i]).collect());
+                    sets.push((0..n).map(|i| grid[i][n - 1 - i]).collect());
+                }
+                WinRule::Diagonal => (),
+                WinRule::FourCorners if !self.corners.is_empty() => sets.push(
+                    self.corners
+                        .iter()
+                        .map(|(row, col)| grid[*row][*col])
+                        .collect(),
+                ),
+                WinRule::FourCorners => (),
+                WinRule::FullCard => sets.push(grid.iter().flatten().copied().collect()),
+            }
+        }
+
+        sets.iter_mut().for_each(|set| set.sort_unstable());
+        sets.sort();
+        sets.dedup();
+
+        sets
+    }
 }
 
 impl fmt::Debug for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut matrix = [[0; 5]; 5];
+        let mut matrix: Vec<Vec<usize>> = self.mask.iter().map(|row| vec![0; row.len()]).collect();
 
         self.numbers
             .iter()
@@ -77,10 +250,10 @@ impl fmt::Debug for Board {
 
         let mask_string = self
             .mask
-            .into_iter()
+            .iter()
             .map(|row| {
-                row.into_iter()
-                    .map(|num| if num { 1 } else { 0 })
+                row.iter()
+                    .map(|num| if *num { 1 } else { 0 })
                     .map(|num| num.to_string())
                     .collect::<Vec<String>>()
                     .join(" ")
@@ -96,6 +269,268 @@ impl fmt::Debug for Board {
 pub struct Game {
     pub numbers: Vec<usize>,
     pub boards: Vec<Board>,
+    pub rules: Vec<WinRule>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Win {
+    pub board: usize,
+    pub score: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Draw {
+    pub number: usize,
+    pub wins: Vec<Win>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrawOrder {
+    pub numbers: Vec<usize>,
+    // the board wins on this draw, counting from 1
+    pub wins_after: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timeline {
+    pub draws: Vec<Draw>,
+    pub never_won: Vec<usize>,
+}
+
+impl Timeline {
+    pub fn wins(&self) -> impl Iterator<Item = &Win> {
+        self.draws.iter().flat_map(|draw| draw.wins.iter())
+    }
+
+    pub fn first_win(&self) -> Option<&Win> {
+        self.wins().next()
+    }
+
+    pub fn last_win(&self) -> Option<&Win> {
+        self.wins().last()
+    }
+}
+
+impl Game {
+    pub fn with_rules(self, rules: &[WinRule]) -> Self {
+        Self {
+            rules: rules.to_vec(),
+            ..self
+        }
+    }
+
+    // A board can only win first by completing one of its winning sets, and drawing exactly that
+    // set marks as much as possible on the other boards. So the board can be made to win first
+    // iff one of its sets, drawn on its own, completes no other board; the smallest such set
+    // gives the minimum number of draws and the rest of the numbers can follow in any order.
+    pub fn rig(&self, board: usize) -> Option<DrawOrder> {
+        let mut sets = self.boards[board].winning_sets(&self.rules);
+        sets.sort_by_key(|set| set.len());
+
+        sets.into_iter()
+            .filter(|set| set.iter().all(|number| self.numbers.contains(number)))
+            .find(|set| {
+                self.boards
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != board)
+                    .all(|(_, b)| {
+                        let mut b = b.clone();
+                        set.iter().for_each(|number| {
+                            b.mark(number);
+                        });
+                        !b.check_rules(&self.rules)
+                    })
+            })
+            .map(|set| {
+                let mut rest = self.numbers.clone();
+                set.iter().for_each(|number| {
+                    rest.remove(rest.iter().position(|n| n == number).unwrap());
+                });
+
+                DrawOrder {
+                    wins_after: set.len(),
+                    numbers: set.into_iter().chain(rest).collect(),
+                }
+            })
+    }
+
+    pub fn min_draws_to_win_first(&self, board: usize) -> Option<usize> {
+        self.rig(board).map(|order| order.wins_after)
+    }
+
+    // maps every number to the (board, row, col) cells it appears in, ordered by board
+    pub fn index(&self) -> HashMap<usize, Vec<(usize, usize, usize)>> {
+        let mut index: HashMap<usize, Vec<(usize, usize, usize)>> = HashMap::new();
+        self.boards.iter().enumerate().for_each(|(board, b)| {
+            b.numbers.iter().for_each(|(number, (row, col))| {
+                index.entry(*number).or_default().push((board, *row, *col))
+            })
+        });
+
+        index
+    }
+
+    pub fn play(&self) -> Timeline {
+        let index = self.index();
+        let mut boards = self.boards.clone();
+        let mut won = vec![false; boards.len()];
+
+        let draws = self
+            .numbers
+            .iter()
+            .map(|number| {
+                // only the boards containing the number are touched, and boards that already won
+                // are out of the game
+                let mut wins = Vec::new();
+                for (board, row, col) in index.get(number).into_iter().flatten() {
+                    let b = &mut boards[*board];
+                    if won[*board] || !b.mark_at(*row, *col) {
+                        continue;
+                    }
+
+                    if b.check_rules_at(*row, *col, &self.rules) {
+                        won[*board] = true;
+                        wins.push(Win {
+                            board: *board,
+                            score: b.calculate_score() * number,
+                        });
+                    }
+                }
+
+                Draw {
+                    number: *number,
+                    wins,
+                }
+            })
+            .collect();
+
+        let never_won = (0..boards.len()).filter(|board| !won[*board]).collect();
+
+        Timeline { draws, never_won }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub p: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Estimate {
+    // Wilson score interval, which behaves well even when a board (almost) never wins
+    fn new(hits: usize, trials: usize, z: f64) -> Self {
+        if trials == 0 {
+            return Estimate {
+                p: 0.0,
+                low: 0.0,
+                high: 1.0,
+            };
+        }
+
+        let n = trials as f64;
+        let p = hits as f64 / n;
+        let denominator = 1.0 + z * z / n;
+        let centre = (p + z * z / (2.0 * n)) / denominator;
+        let spread = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+
+        Estimate {
+            p,
+            low: (centre - spread).max(0.0),
+            high: (centre + spread).min(1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WinOdds {
+    // the board wins first (or last) on its own
+    pub first: Estimate,
+    pub last: Estimate,
+    // the board completes on the first (or last) winning draw together with other boards
+    pub first_tied: Estimate,
+    pub last_tied: Estimate,
+}
+
+#[derive(Clone, Debug)]
+pub struct MonteCarlo {
+    pub trials: usize,
+    pub seed: u64,
+    pub threads: usize,
+    // 1.96 for a 95% confidence interval
+    pub z: f64,
+}
+
+impl MonteCarlo {
+    pub fn new(trials: usize, seed: u64) -> Self {
+        Self {
+            trials,
+            seed,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            z: 1.96,
+        }
+    }
+
+    // Plays the game under uniformly random draw orders. Every trial shuffles the original order
+    // with its own generator derived from the seed, so the results don't depend on the number of
+    // threads.
+    pub fn estimate(&self, game: &Game) -> Vec<WinOdds> {
+        let boards = game.boards.len();
+        let threads = self.threads.clamp(1, self.trials.max(1));
+        let chunk = self.trials.div_ceil(threads);
+
+        let counts: Vec<Vec<[usize; 4]>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let trials = (t * chunk)..((t + 1) * chunk).min(self.trials);
+                    scope.spawn(move || {
+                        let mut shuffled = game.clone();
+                        let mut counts = vec![[0; 4]; boards];
+                        for trial in trials {
+                            shuffled.numbers.clone_from(&game.numbers);
+                            Rng::new(self.seed.wrapping_add(trial as u64))
+                                .shuffle(&mut shuffled.numbers);
+
+                            let timeline = shuffled.play();
+                            let mut winning = timeline.draws.iter().filter(|d| !d.wins.is_empty());
+                            let first = winning.next();
+                            let last = winning.next_back().or(first);
+
+                            for (draw, alone, tied) in [(first, 0, 2), (last, 1, 3)] {
+                                let wins = draw.map_or(&[][..], |draw| &draw.wins[..]);
+                                let slot = if wins.len() == 1 { alone } else { tied };
+                                wins.iter().for_each(|win| counts[win.board][slot] += 1);
+                            }
+                        }
+
+                        counts
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        (0..boards)
+            .map(|board| {
+                let hits = counts.iter().fold([0; 4], |mut total, c| {
+                    (0..4).for_each(|slot| total[slot] += c[board][slot]);
+                    total
+                });
+
+                WinOdds {
+                    first: Estimate::new(hits[0], self.trials, self.z),
+                    last: Estimate::new(hits[1], self.trials, self.z),
+                    first_tied: Estimate::new(hits[2], self.trials, self.z),
+                    last_tied: Estimate::new(hits[3], self.trials, self.z),
+                }
+            })
+            .collect()
+    }
 }
 
 #[aoc_generator(day4)]
@@ -109,56 +544,21 @@ pub fn generator(input: &str) -> Game {
         .map(|board| Board::new(board))
         .collect();
 
-    Game { numbers, boards }
+    Game {
+        numbers,
+        boards,
+        rules: CLASSIC_RULES.to_vec(),
+    }
 }
 
 #[aoc(day4, part1)]
 pub fn solver_1(game: &Game) -> usize {
-    let mut game = game.clone(); // Since cargo-aoc gives us an immutable reference
-
-    for number in game.numbers.iter() {
-        // mark the number on all the boards
-        game.boards.iter_mut().for_each(|board| {
-            board.mark(number);
-        });
-
-        // find the first board that wins
-        let board_find = game.boards.iter_mut().find(|board| board.check());
-        match board_find {
-            Some(board) => {
-                return board.calculate_score() * number;
-            }
-            None => {
-                continue;
-            }
-        }
-    }
-
-    unreachable!()
+    game.play().first_win().unwrap().score
 }
 
 #[aoc(day4, part2)]
 pub fn solver_2(game: &Game) -> usize {
-    let mut game = game.clone(); // Since cargo-aoc gives us an immutable reference
-    let mut last_score = None;
-
-    for number in game.numbers.iter() {
-        // mark the number on all the boards
-        for board in game.boards.iter_mut() {
-            board.mark(number);
-        }
-
-        // find the score of the last board that won
-        match game.boards.iter().rfind(|board| board.check()) {
-            Some(board) => last_score = Some(board.calculate_score() * number),
-            None => (),
-        }
-
-        // remove all the boards that won
-        game.boards.retain(|board| !board.check())
-    }
-
-    last_score.unwrap()
+    game.play().last_win().unwrap().score
 }
 
 #[cfg(test)]
@@ -200,4 +600,275 @@ mod test {
 
         assert_eq!(1924, result);
     }
+
+    fn marked(board: &str, numbers: &[usize]) -> Board {
+        let mut board = Board::new(board);
+        numbers.iter().for_each(|number| {
+            board.mark(number);
+        });
+
+        board
+    }
+
+    const SMALL: &str = r"1 2 3
+4 5 6
+7 8 9";
+
+    #[test]
+    fn test_small_board() {
+        let board = Board::new(SMALL);
+
+        assert_eq!((board.rows(), board.cols()), (3, 3));
+        assert!(marked(SMALL, &[4, 5, 6]).check());
+        assert!(marked(SMALL, &[2, 5, 8]).check());
+        assert!(!marked(SMALL, &[1, 5, 9]).check());
+    }
+
+    #[test]
+    fn test_win_rules() {
+        let diagonal = marked(SMALL, &[1, 5, 9]);
+        assert!(diagonal.check_rules(&[WinRule::Diagonal]));
+        assert!(marked(SMALL, &[3, 5, 7]).check_rules(&[WinRule::Diagonal]));
+
+        let corners = marked(SMALL, &[1, 3, 7, 9]);
+        assert!(corners.check_rules(&[WinRule::FourCorners]));
+        assert!(!corners.check_rules(&[WinRule::Row, WinRule::Column, WinRule::Diagonal]));
+
+        let almost_full = marked(SMALL, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(!almost_full.check_rules(&[WinRule::FullCard]));
+        assert!(marked(SMALL, &[1, 2, 3, 4, 5, 6, 7, 8, 9]).check_rules(&[WinRule::FullCard]));
+    }
+
+    #[test]
+    fn test_irregular_board() {
+        let board = r"1 2 3 4
+5 6
+7 8 9";
+
+        assert_eq!(Board::new(board).cols(), 4);
+        assert!(marked(board, &[5, 6]).check());
+        // only the first two columns run through every row
+        assert!(!marked(board, &[4]).check_rules(&[WinRule::Column]));
+        assert!(!marked(board, &[3, 9]).check_rules(&[WinRule::Column]));
+        assert!(marked(board, &[2, 6, 8]).check_rules(&[WinRule::Column]));
+        assert_eq!(
+            Board::new(board).winning_sets(&[WinRule::Column]),
+            vec![vec![1, 5, 7], vec![2, 6, 8]]
+        );
+        assert!(!marked(board, &[1, 5, 9]).check_rules(&[WinRule::Diagonal]));
+        assert!(marked(board, &[1, 4, 7, 9]).check_rules(&[WinRule::FourCorners]));
+    }
+
+    #[test]
+    fn test_game_rules() {
+        let game = generator(INPUT);
+
+        // the third board completes its anti-diagonal when 2 is drawn
+        assert_eq!(
+            solver_1(&game.clone().with_rules(&[WinRule::Diagonal])),
+            494
+        );
+        // the second board is the first to be fully marked, leaving nothing unmarked
+        assert_eq!(solver_1(&game.with_rules(&[WinRule::FullCard])), 0);
+    }
+
+    #[test]
+    fn test_timeline() {
+        let timeline = generator(INPUT).play();
+
+        assert_eq!(timeline.draws.len(), 27);
+        assert!(timeline.never_won.is_empty());
+        assert_eq!(
+            timeline.wins().map(|win| win.board).collect::<Vec<usize>>(),
+            vec![2, 0, 1]
+        );
+
+        let draw = timeline
+            .draws
+            .iter()
+            .find(|draw| draw.number == 24)
+            .unwrap();
+        assert_eq!(
+            draw.wins,
+            vec![Win {
+                board: 2,
+                score: 4512
+            }]
+        );
+    }
+
+    #[test]
+    fn test_timeline_never_won() {
+        let game = Game {
+            numbers: vec![1, 2, 3],
+            boards: vec![Board::new("1 2\n3 4"), Board::new("5 6\n7 8")],
+            rules: CLASSIC_RULES.to_vec(),
+        };
+        let timeline = game.play();
+
+        assert_eq!(
+            timeline.draws[1].wins,
+            vec![Win {
+                board: 0,
+                score: 14
+            }]
+        );
+        assert_eq!(timeline.never_won, vec![1]);
+        assert_eq!(timeline.first_win(), timeline.last_win());
+    }
+
+    #[test]
+    fn test_play_matches_rescan() {
+        let rule_sets: [&[WinRule]; 4] = [
+            CLASSIC_RULES,
+            &[WinRule::Diagonal],
+            &[WinRule::FourCorners, WinRule::Row],
+            &[WinRule::FullCard],
+        ];
+
+        for rules in rule_sets {
+            let game = generator(INPUT).with_rules(rules);
+            let mut boards = game.boards.clone();
+            let mut expected = Vec::new();
+            for number in game.numbers.iter() {
+                for (board, b) in boards.iter_mut().enumerate() {
+                    if !b.check_rules(rules) {
+                        b.mark(number);
+                        if b.check_rules(rules) {
+                            expected.push((board, b.calculate_score() * number));
+                        }
+                    }
+                }
+            }
+
+            let timeline = game.play();
+            let actual: Vec<(usize, usize)> =
+                timeline.wins().map(|win| (win.board, win.score)).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    fn check_rigged(game: &Game, board: usize, wins_after: usize) {
+        let order = game.rig(board).unwrap();
+        assert_eq!(order.wins_after, wins_after);
+
+        let mut numbers = order.numbers.clone();
+        numbers.sort_unstable();
+        let mut expected = game.numbers.clone();
+        expected.sort_unstable();
+        assert_eq!(numbers, expected);
+
+        let rigged = Game {
+            numbers: order.numbers,
+            ..game.clone()
+        };
+        let draw = rigged
+            .play()
+            .draws
+            .into_iter()
+            .find(|draw| !draw.wins.is_empty())
+            .unwrap();
+        assert_eq!(draw.number, rigged.numbers[wins_after - 1]);
+        assert_eq!(
+            draw.wins
+                .iter()
+                .map(|win| win.board)
+                .collect::<Vec<usize>>(),
+            vec![board]
+        );
+    }
+
+    #[test]
+    fn test_rig() {
+        let game = generator(INPUT);
+        (0..3).for_each(|board| check_rigged(&game, board, 5));
+
+        let game = game.with_rules(&[WinRule::FourCorners]);
+        (0..3).for_each(|board| check_rigged(&game, board, 4));
+    }
+
+    #[test]
+    fn test_rig_impossible() {
+        let game = Game {
+            numbers: (1..=9).collect(),
+            boards: vec![Board::new(SMALL), Board::new("1 2\n4 5"), Board::new(SMALL)],
+            rules: CLASSIC_RULES.to_vec(),
+        };
+
+        // the 2x2 board is contained in the top left corner of the others, so it can win first
+        check_rigged(&game, 1, 2);
+        // but the two identical boards always win together
+        assert_eq!(game.min_draws_to_win_first(0), None);
+        assert_eq!(game.rig(2), None);
+
+        // numbers that never get drawn can't be used to win
+        let game = Game {
+            numbers: vec![1, 2, 3],
+            boards: vec![Board::new("1 4\n5 6"), Board::new("2 7\n3 8")],
+            rules: vec![WinRule::Column],
+        };
+        assert_eq!(game.min_draws_to_win_first(0), None);
+        assert_eq!(game.min_draws_to_win_first(1), Some(2));
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let game = generator(INPUT);
+        let odds = MonteCarlo::new(500, 2021).estimate(&game);
+
+        assert_eq!(odds.len(), 3);
+        // every shuffle has a first and a last winning draw, shared by two boards or more on a tie
+        let total_first: f64 = odds.iter().map(|o| o.first.p).sum();
+        let total_last: f64 = odds.iter().map(|o| o.last.p).sum();
+        let tied_first: f64 = odds.iter().map(|o| o.first_tied.p).sum();
+        let tied_last: f64 = odds.iter().map(|o| o.last_tied.p).sum();
+        assert!(total_first <= 1.0 + 1e-9 && total_first + tied_first / 2.0 >= 1.0 - 1e-9);
+        assert!(total_last <= 1.0 + 1e-9 && total_last + tied_last / 2.0 >= 1.0 - 1e-9);
+        odds.iter().for_each(|o| {
+            assert!(o.first.low <= o.first.p && o.first.p <= o.first.high);
+            assert!(o.last.low <= o.last.p && o.last.p <= o.last.high);
+        });
+
+        // the same seed gives the same answer however the work is split up
+        let single = MonteCarlo {
+            threads: 1,
+            ..MonteCarlo::new(500, 2021)
+        };
+        let split = MonteCarlo {
+            threads: 4,
+            ..MonteCarlo::new(500, 2021)
+        };
+        assert_eq!(single.estimate(&game), split.estimate(&game));
+    }
+
+    #[test]
+    fn test_monte_carlo_ties() {
+        // identical boards always complete together, so neither ever wins on its own
+        let game = Game {
+            numbers: (1..=4).collect(),
+            boards: vec![Board::new("1 2\n3 4"), Board::new("1 2\n3 4")],
+            rules: CLASSIC_RULES.to_vec(),
+        };
+        let odds = MonteCarlo::new(100, 31).estimate(&game);
+
+        odds.iter().for_each(|o| {
+            assert_eq!((o.first.p, o.last.p), (0.0, 0.0));
+            assert_eq!((o.first_tied.p, o.last_tied.p), (1.0, 1.0));
+        });
+    }
+
+    #[test]
+    fn test_monte_carlo_symmetric() {
+        let game = Game {
+            numbers: (1..=8).collect(),
+            boards: vec![Board::new("1 2\n3 4"), Board::new("5 6\n7 8")],
+            rules: CLASSIC_RULES.to_vec(),
+        };
+        let odds = MonteCarlo::new(2000, 7).estimate(&game);
+
+        odds.iter().for_each(|o| {
+            assert!(o.first.low < 0.5 && 0.5 < o.first.high);
+            assert!(o.last.low < 0.5 && 0.5 < o.last.high);
+        });
+    }
 }
@@ -1,14 +1,21 @@
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
-use std::{cmp, error::Error};
+use std::{cmp, error::Error, io};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Point {
     x: isize,
     y: isize,
 }
 
+impl Point {
+    pub fn new(x: isize, y: isize) -> Self {
+        Self { x, y }
+    }
+}
+
 impl FromStr for Point {
     type Err = Box<dyn Error>;
 
@@ -21,7 +28,7 @@ impl FromStr for Point {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Line {
     start: Point,
     end: Point,
@@ -44,57 +51,142 @@ impl Line {
         }
     }
 
-    fn slope(&self) -> (isize, isize) {
-        (
-            match cmp::Ord::cmp(&self.start.x, &self.end.x) {
-                cmp::Ordering::Less => -1,
-                cmp::Ordering::Equal => 0,
-                cmp::Ordering::Greater => 1,
-            },
-            match cmp::Ord::cmp(&self.start.y, &self.end.y) {
-                cmp::Ordering::Less => -1,
-                cmp::Ordering::Equal => 0,
-                cmp::Ordering::Greater => 1,
-            },
-        )
+    fn is_diagonal(&self) -> bool {
+        (self.end.x - self.start.x).abs() == (self.end.y - self.start.y).abs()
+    }
+
+    // the same line, drawn from its leftmost end (the lower one if vertical)
+    pub fn canonical(&self) -> Line {
+        if (self.start.x, self.start.y) <= (self.end.x, self.end.y) {
+            self.clone()
+        } else {
+            Line {
+                start: self.end.clone(),
+                end: self.start.clone(),
+            }
+        }
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        if self.is_horizontal() {
+            Orientation::Horizontal
+        } else if self.is_vertical() {
+            Orientation::Vertical
+        } else if self.is_diagonal() {
+            Orientation::Diagonal
+        } else {
+            Orientation::Sloped
+        }
+    }
+
+    // smallest step from `start` towards `end` which lands on a lattice point, (0, 0) if the line
+    // is a single point
+    fn step(&self) -> (isize, isize) {
+        let (dx, dy) = (self.end.x - self.start.x, self.end.y - self.start.y);
+        match gcd(dx, dy) {
+            0 => (0, 0),
+            g => (dx / g, dy / g),
+        }
+    }
+
+    // number of lattice points on the line, ends included
+    fn len(&self) -> usize {
+        gcd(self.end.x - self.start.x, self.end.y - self.start.y) as usize + 1
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        let (dx, dy) = self.step();
+        (0..self.len() as isize).map(move |i| Point {
+            x: self.start.x + i * dx,
+            y: self.start.y + i * dy,
+        })
+    }
+
+    // the points of the line inside the inclusive box from `min` to `max`, found without walking
+    // the ones outside it
+    fn points_within(&self, min: &Point, max: &Point) -> impl Iterator<Item = Point> + '_ {
+        let (dx, dy) = self.step();
+        let (mut first, mut last) = (0, self.len() as isize - 1);
+        for (start, step, low, high) in [
+            (self.start.x, dx, min.x, max.x),
+            (self.start.y, dy, min.y, max.y),
+        ] {
+            if step == 0 {
+                if start < low || start > high {
+                    last = -1;
+                }
+                continue;
+            }
+
+            // how many steps it takes to get from the start to either side of the box
+            let (step, from, to) = if step > 0 {
+                (step, low - start, high - start)
+            } else {
+                (-step, start - high, start - low)
+            };
+            first = cmp::max(first, -(-from).div_euclid(step));
+            last = cmp::min(last, to.div_euclid(step));
+        }
+
+        (first..=last).map(move |i| Point {
+            x: self.start.x + i * dx,
+            y: self.start.y + i * dy,
+        })
     }
 
     fn contains(&self, p: &Point) -> bool {
-        // points at the beginning and the end of the line will give weird slopes
-        // so we take care of this first
+        // this also takes care of lines which are a single point, where there is no step
         if &self.start == p || p == &self.end {
             return true;
         }
 
-        let to_p = Line {
-            start: self.start.clone(),
-            end: p.clone(),
-        };
+        let (dx, dy) = self.step();
+        let (px, py) = (p.x - self.start.x, p.y - self.start.y);
 
-        let slope = self.slope();
-        let to_p_slope = to_p.slope();
-        if slope != to_p_slope {
+        // `p` has to be in the direction of the line, and since the step is as small as it gets
+        // it's then a whole number of steps away from the start
+        if (dx, dy) == (0, 0) || px * dy != py * dx {
             return false;
         }
+        let steps = if dx != 0 { px / dx } else { py / dy };
 
-        // these combined with the above slope check confirm that the point `p` is inside the rectangle
-        // created by `self.start` and `self.end`
-        let x_bounds = (p.x - self.start.x).abs() < (self.end.x - self.start.x).abs();
-        let y_bounds = (p.y - self.start.y).abs() < (self.end.y - self.start.y).abs();
+        0 < steps && (steps as usize) < self.len()
+    }
+}
 
-        if slope.0 == 0 {
-            y_bounds
-        } else if slope.1 == 0 {
-            x_bounds
-        } else {
-            x_bounds
-                && y_bounds
-                && ((p.x - self.start.x) / slope.0 == (p.y - self.start.y) / slope.1)
-            // checks if the point is on the line
-        }
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+    // at 45 degrees
+    Diagonal,
+    // anything else
+    Sloped,
+}
+
+pub const PART_1_ORIENTATIONS: &[Orientation] = &[Orientation::Horizontal, Orientation::Vertical];
+pub const PART_2_ORIENTATIONS: &[Orientation] = &[
+    Orientation::Horizontal,
+    Orientation::Vertical,
+    Orientation::Diagonal,
+];
+
+pub fn filter_orientations(lines: &[Line], allowed: &[Orientation]) -> Vec<Line> {
+    lines
+        .iter()
+        .filter(|line| allowed.contains(&line.orientation()))
+        .cloned()
+        .collect()
+}
+
 impl FromStr for Line {
     type Err = Box<dyn Error>;
 
@@ -107,27 +199,327 @@ impl FromStr for Line {
     }
 }
 
+// A small reader for the bits of Well-Known Text we care about: POINT, LINESTRING and their MULTI
+// versions, with integer coordinates.
+struct Wkt<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Wkt<'a> {
+    fn new(s: &'a str) -> Self {
+        Self { rest: s.trim() }
+    }
+
+    fn is_done(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Box<dyn Error>> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at \"{}\"", c, self.rest).into())
+        }
+    }
+
+    fn word(&mut self) -> String {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(self.rest.len());
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+
+        word.to_ascii_uppercase()
+    }
+
+    // `EMPTY` or an opening parenthesis, so callers know whether to read anything
+    fn open(&mut self) -> Result<bool, Box<dyn Error>> {
+        self.skip_whitespace();
+        if self.peek() == Some('(') {
+            self.expect('(')?;
+            return Ok(true);
+        }
+
+        match self.word().as_str() {
+            "EMPTY" => Ok(false),
+            _ => Err(format!("expected '(' or EMPTY at \"{}\"", self.rest).into()),
+        }
+    }
+
+    fn number(&mut self) -> Result<isize, Box<dyn Error>> {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(self.rest.len());
+        let (number, rest) = self.rest.split_at(end);
+        self.rest = rest;
+
+        // GIS tools like to write whole numbers as floats
+        match number.parse::<isize>() {
+            Ok(n) => Ok(n),
+            Err(_) => match number.parse::<f64>() {
+                Ok(f) if f.fract() == 0.0 && f.abs() < isize::MAX as f64 => Ok(f as isize),
+                _ => Err(format!("\"{}\" is not a whole number", number).into()),
+            },
+        }
+    }
+
+    fn point(&mut self) -> Result<Point, Box<dyn Error>> {
+        let x = self.number()?;
+        let y = self.number()?;
+
+        Ok(Point { x, y })
+    }
+
+    // the parenthesised, comma separated points of a LINESTRING, after the keyword
+    fn points(&mut self) -> Result<Vec<Point>, Box<dyn Error>> {
+        if !self.open()? {
+            return Ok(Vec::new());
+        }
+
+        let mut points = vec![self.point()?];
+        while self.eat(',') {
+            points.push(self.point()?);
+        }
+        self.expect(')')?;
+
+        Ok(points)
+    }
+
+    // MULTIPOINT allows its points with and without their own parentheses
+    fn multi_point(&mut self) -> Result<Vec<Point>, Box<dyn Error>> {
+        if !self.open()? {
+            return Ok(Vec::new());
+        }
+
+        let mut points = Vec::new();
+        loop {
+            if self.eat('(') {
+                points.push(self.point()?);
+                self.expect(')')?;
+            } else {
+                points.push(self.point()?);
+            }
+
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(')')?;
+
+        Ok(points)
+    }
+
+    fn line_strings(&mut self) -> Result<Vec<Vec<Point>>, Box<dyn Error>> {
+        match self.word().as_str() {
+            "LINESTRING" => Ok(vec![self.points()?]),
+            "MULTILINESTRING" => {
+                if !self.open()? {
+                    return Ok(Vec::new());
+                }
+
+                let mut line_strings = vec![self.points()?];
+                while self.eat(',') {
+                    line_strings.push(self.points()?);
+                }
+                self.expect(')')?;
+
+                Ok(line_strings)
+            }
+            word => {
+                Err(format!("expected LINESTRING or MULTILINESTRING, found \"{}\"", word).into())
+            }
+        }
+    }
+}
+
+fn wkt_coords(p: &Point) -> String {
+    format!("{} {}", p.x, p.y)
+}
+
+impl Point {
+    pub fn to_wkt(&self) -> String {
+        format!("POINT({})", wkt_coords(self))
+    }
+
+    pub fn from_wkt(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut wkt = Wkt::new(s);
+        if wkt.word() != "POINT" {
+            return Err(format!("expected a POINT, found \"{}\"", s).into());
+        }
+        wkt.expect('(')?;
+        let p = wkt.point()?;
+        wkt.expect(')')?;
+
+        if !wkt.is_done() {
+            return Err(format!("unexpected \"{}\" after the POINT", wkt.rest).into());
+        }
+
+        Ok(p)
+    }
+}
+
+impl Line {
+    pub fn to_wkt(&self) -> String {
+        format!(
+            "LINESTRING({}, {})",
+            wkt_coords(&self.start),
+            wkt_coords(&self.end)
+        )
+    }
+
+    // only a LINESTRING with exactly two points is a single line
+    pub fn from_wkt(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut wkt = Wkt::new(s);
+        if wkt.word() != "LINESTRING" {
+            return Err(format!("expected a LINESTRING, found \"{}\"", s).into());
+        }
+        let mut points = wkt.points()?;
+
+        if !wkt.is_done() {
+            return Err(format!("unexpected \"{}\" after the LINESTRING", wkt.rest).into());
+        }
+        if points.len() != 2 {
+            return Err(format!("expected 2 points in \"{}\", found {}", s, points.len()).into());
+        }
+        let end = points.pop().unwrap();
+        let start = points.pop().unwrap();
+
+        Ok(Self { start, end })
+    }
+}
+
+// Reads any number of LINESTRING and MULTILINESTRING geometries, one after the other. Line strings
+// with more than two points are split into their segments.
+pub fn lines_from_wkt(s: &str) -> Result<Vec<Line>, Box<dyn Error>> {
+    let mut wkt = Wkt::new(s);
+    let mut lines = Vec::new();
+    while !wkt.is_done() {
+        for points in wkt.line_strings()? {
+            if points.len() == 1 {
+                return Err("a LINESTRING needs at least two points".into());
+            }
+            lines.extend(points.windows(2).map(|pair| Line {
+                start: pair[0].clone(),
+                end: pair[1].clone(),
+            }));
+        }
+        wkt.skip_whitespace();
+    }
+
+    Ok(lines)
+}
+
+pub fn lines_to_wkt(lines: &[Line]) -> String {
+    if lines.is_empty() {
+        return "MULTILINESTRING EMPTY".to_string();
+    }
+
+    let line_strings = lines
+        .iter()
+        .map(|line| format!("({}, {})", wkt_coords(&line.start), wkt_coords(&line.end)))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("MULTILINESTRING({})", line_strings)
+}
+
+pub fn points_from_wkt(s: &str) -> Result<Vec<Point>, Box<dyn Error>> {
+    let mut wkt = Wkt::new(s);
+    if wkt.word() != "MULTIPOINT" {
+        return Err(format!("expected a MULTIPOINT, found \"{}\"", s).into());
+    }
+    let points = wkt.multi_point()?;
+
+    if !wkt.is_done() {
+        return Err(format!("unexpected \"{}\" after the MULTIPOINT", wkt.rest).into());
+    }
+
+    Ok(points)
+}
+
+// for exporting overlaps, e.g. `VentIndex::points_covered_at_least`
+pub fn points_to_wkt(points: &[Point]) -> String {
+    if points.is_empty() {
+        return "MULTIPOINT EMPTY".to_string();
+    }
+
+    let points = points
+        .iter()
+        .map(|p| format!("({})", wkt_coords(p)))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("MULTIPOINT({})", points)
+}
+
 #[aoc_generator(day5)]
 pub fn generator(input: &str) -> Result<Vec<Line>, Box<dyn Error>> {
+    let keyword = input.trim_start().to_ascii_uppercase();
+    if keyword.starts_with("LINESTRING") || keyword.starts_with("MULTILINESTRING") {
+        return lines_from_wkt(input);
+    }
+
     input.lines().map(|line| Ok(line.trim().parse()?)).collect()
 }
 
-fn count_intersections_imperative(lines: &Vec<Line>) -> usize {
-    let max_x = lines
-        .iter()
-        .map(|line| cmp::max(line.start.x, line.end.x))
-        .max()
-        .unwrap();
-    let max_y = lines
-        .iter()
-        .map(|line| cmp::max(line.start.y, line.end.y))
-        .max()
-        .unwrap();
+// the scanning counters below test every point of the bounding box, so they give up on boxes
+// with more points than this unless told otherwise
+pub const MAX_SCAN_AREA: usize = 1 << 24;
+
+fn scan_region(lines: &[Line], max_area: usize) -> Result<Option<(Point, Point)>, Box<dyn Error>> {
+    let (min, max) = match bounding_box(lines) {
+        Some(bounds) => bounds,
+        None => return Ok(None),
+    };
+
+    let area = (max.x.abs_diff(min.x) as u128 + 1) * (max.y.abs_diff(min.y) as u128 + 1);
+    if area > max_area as u128 {
+        return Err(format!(
+            "bounding box from {},{} to {},{} has {} points, more than the {} allowed",
+            min.x, min.y, max.x, max.y, area, max_area
+        )
+        .into());
+    }
+
+    Ok(Some((min, max)))
+}
+
+fn count_intersections_imperative(
+    lines: &[Line],
+    max_area: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let (min, max) = match scan_region(lines, max_area)? {
+        Some(bounds) => bounds,
+        None => return Ok(0),
+    };
 
     let mut res = 0;
 
-    for x in 0..=max_x {
-        for y in 0..=max_y {
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
             let mut count = 0;
             for line in lines.iter() {
                 if line.contains(&Point { x, y }) {
@@ -143,24 +535,20 @@ fn count_intersections_imperative(lines: &Vec<Line>) -> usize {
         }
     }
 
-    res
+    Ok(res)
 }
 
-fn count_intersections_functional(lines: &Vec<Line>) -> usize {
-    let max_x = lines
-        .iter()
-        .map(|line| cmp::max(line.start.x, line.end.x))
-        .max()
-        .unwrap() as usize;
-    let max_y = lines
-        .iter()
-        .map(|line| cmp::max(line.start.y, line.end.y))
-        .max()
-        .unwrap() as usize;
+fn count_intersections_functional(
+    lines: &[Line],
+    max_area: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let (min, max) = match scan_region(lines, max_area)? {
+        Some(bounds) => bounds,
+        None => return Ok(0),
+    };
 
-    (0..=max_x)
-        .map(|x| (0..=max_y).map(move |y| (x as isize, y as isize)))
-        .flatten()
+    Ok((min.x..=max.x)
+        .flat_map(|x| (min.y..=max.y).map(move |y| (x, y)))
         .filter(|(x, y)| {
             lines
                 .iter()
@@ -168,44 +556,639 @@ fn count_intersections_functional(lines: &Vec<Line>) -> usize {
                 .count()
                 > 1
         })
-        .count()
+        .count())
 }
 
-#[aoc(day5, part1, Imperative)]
-pub fn solver_1_imperative(lines: &Vec<Line>) -> usize {
-    let lines = lines
+// how much bigger than the total length of the lines the bounding box may be before it's not worth
+// allocating a cell for every point in it
+const DENSE_FACTOR: usize = 4;
+
+#[derive(Debug, Clone)]
+pub enum Coverage {
+    Dense {
+        min: Point,
+        width: usize,
+        counts: Vec<usize>,
+    },
+    Sparse(HashMap<Point, usize>),
+}
+
+impl Coverage {
+    pub fn rasterize(lines: &[Line]) -> Self {
+        let total: usize = lines.iter().map(|line| line.len()).sum();
+        let (min, max) = match bounding_box(lines) {
+            Some(bounds) => bounds,
+            None => return Coverage::Sparse(HashMap::new()),
+        };
+        let width = (max.x - min.x) as usize + 1;
+        let height = (max.y - min.y) as usize + 1;
+
+        match width.checked_mul(height) {
+            Some(area) if area <= total.saturating_mul(DENSE_FACTOR) => {
+                let mut counts = vec![0; area];
+                lines.iter().flat_map(|line| line.points()).for_each(|p| {
+                    counts[(p.y - min.y) as usize * width + (p.x - min.x) as usize] += 1
+                });
+
+                Coverage::Dense { min, width, counts }
+            }
+            _ => {
+                let mut counts: HashMap<Point, usize> = HashMap::with_capacity(total);
+                lines
+                    .iter()
+                    .flat_map(|line| line.points())
+                    .for_each(|p| *counts.entry(p).or_insert(0) += 1);
+
+                Coverage::Sparse(counts)
+            }
+        }
+    }
+
+    pub fn get(&self, p: &Point) -> usize {
+        match self {
+            Coverage::Dense { min, width, counts } => {
+                if p.x < min.x || p.y < min.y || (p.x - min.x) as usize >= *width {
+                    return 0;
+                }
+                let index = (p.y - min.y) as usize * width + (p.x - min.x) as usize;
+                counts.get(index).copied().unwrap_or(0)
+            }
+            Coverage::Sparse(counts) => counts.get(p).copied().unwrap_or(0),
+        }
+    }
+
+    // number of points covered by at least `k` lines
+    pub fn count_at_least(&self, k: usize) -> usize {
+        match self {
+            Coverage::Dense { counts, .. } => counts.iter().filter(|c| **c >= k).count(),
+            Coverage::Sparse(counts) => counts.values().filter(|c| **c >= k).count(),
+        }
+    }
+}
+
+fn bounding_box(lines: &[Line]) -> Option<(Point, Point)> {
+    let xs = lines.iter().flat_map(|line| [line.start.x, line.end.x]);
+    let ys = lines.iter().flat_map(|line| [line.start.y, line.end.y]);
+
+    Some((
+        Point {
+            x: xs.clone().min()?,
+            y: ys.clone().min()?,
+        },
+        Point {
+            x: xs.max()?,
+            y: ys.max()?,
+        },
+    ))
+}
+
+fn count_intersections_rasterized(lines: &[Line]) -> usize {
+    Coverage::rasterize(lines).count_at_least(2)
+}
+
+// Which lines cover each point, for when the count alone isn't enough
+#[derive(Debug, Clone)]
+pub struct VentIndex {
+    lines: Vec<Line>,
+    cells: HashMap<Point, Vec<usize>>,
+}
+
+impl VentIndex {
+    pub fn new(lines: &[Line]) -> Self {
+        let mut cells: HashMap<Point, Vec<usize>> = HashMap::new();
+        lines.iter().enumerate().for_each(|(i, line)| {
+            line.points()
+                .for_each(|p| cells.entry(p).or_default().push(i))
+        });
+
+        Self {
+            lines: lines.to_vec(),
+            cells,
+        }
+    }
+
+    pub fn line(&self, i: usize) -> &Line {
+        &self.lines[i]
+    }
+
+    // sorted by row and then column
+    pub fn points_covered_at_least(&self, k: usize) -> Vec<Point> {
+        let mut points: Vec<Point> = self
+            .cells
+            .iter()
+            .filter(|(_, lines)| lines.len() >= k)
+            .map(|(p, _)| p.clone())
+            .collect();
+        points.sort_unstable_by_key(|p| (p.y, p.x));
+
+        points
+    }
+
+    // indices of the lines through `p`, in increasing order
+    pub fn lines_at(&self, p: &Point) -> &[usize] {
+        self.cells.get(p).map_or(&[], |lines| lines.as_slice())
+    }
+
+    // indices of the indexed lines sharing at least one point with `line`
+    pub fn lines_overlapping(&self, line: &Line) -> Vec<usize> {
+        let mut lines: Vec<usize> = line
+            .points()
+            .flat_map(|p| self.lines_at(&p).iter().copied())
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        lines
+    }
+
+    // indices of the other lines colliding with line `i`
+    pub fn overlapping(&self, i: usize) -> Vec<usize> {
+        let mut lines = self.lines_overlapping(&self.lines[i]);
+        lines.retain(|other| *other != i);
+
+        lines
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    // inclusive corners of the region to draw, the bounding box of the lines if not given
+    pub crop: Option<(Point, Point)>,
+    // every `scale` x `scale` block of points becomes one pixel showing the highest count in it
+    pub scale: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            crop: None,
+            scale: 1,
+        }
+    }
+}
+
+// renders with more pixels than this are refused, like the scans over `MAX_SCAN_AREA`
+pub const MAX_RENDER_PIXELS: usize = 1 << 24;
+
+// Rows of the highest overlap count per pixel. Only the points of the lines inside the region get
+// visited, so cropping a small part out of a huge survey stays cheap.
+fn render(lines: &[Line], options: &RenderOptions) -> Result<Vec<Vec<usize>>, Box<dyn Error>> {
+    let (min, max) = match options.crop.clone().or_else(|| bounding_box(lines)) {
+        Some(bounds) if bounds.0.x <= bounds.1.x && bounds.0.y <= bounds.1.y => bounds,
+        _ => return Ok(Vec::new()),
+    };
+    let scale = options.scale.max(1);
+    let width = max.x.abs_diff(min.x) / scale + 1;
+    let height = max.y.abs_diff(min.y) / scale + 1;
+
+    let pixels = width as u128 * height as u128;
+    if pixels > MAX_RENDER_PIXELS as u128 {
+        return Err(format!(
+            "rendering {},{} to {},{} at scale {} takes {} pixels, more than the {} allowed",
+            min.x, min.y, max.x, max.y, scale, pixels, MAX_RENDER_PIXELS
+        )
+        .into());
+    }
+
+    let mut counts: HashMap<Point, usize> = HashMap::new();
+    lines
         .iter()
-        .filter(|line| line.is_horizontal() || line.is_vertical())
-        .map(|line| line.clone())
-        .collect::<Vec<Line>>();
+        .flat_map(|line| line.points_within(&min, &max))
+        .for_each(|p| *counts.entry(p).or_insert(0) += 1);
+
+    let mut rows = vec![vec![0; width]; height];
+    counts.into_iter().for_each(|(p, count)| {
+        let pixel = &mut rows[p.y.abs_diff(min.y) / scale][p.x.abs_diff(min.x) / scale];
+        *pixel = cmp::max(*pixel, count);
+    });
 
-    count_intersections_imperative(&lines)
+    Ok(rows)
 }
 
-#[aoc(day5, part1, Functional)]
-pub fn solver_1_functional(lines: &Vec<Line>) -> usize {
-    let lines = lines
+// the diagram from the puzzle, `.` for untouched points and the number of lines otherwise
+pub fn render_ascii(lines: &[Line], options: &RenderOptions) -> Result<String, Box<dyn Error>> {
+    Ok(render(lines, options)?
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|count| match count {
+                    0 => '.',
+                    1..=9 => char::from_digit(count as u32, 10).unwrap(),
+                    _ => '#',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+// a binary grayscale PGM where brighter pixels are covered by more lines
+pub fn write_pgm<W: io::Write>(
+    lines: &[Line],
+    options: &RenderOptions,
+    writer: &mut W,
+) -> io::Result<()> {
+    let pixels = render(lines, options)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let (width, height) = (pixels.first().map_or(0, |row| row.len()), pixels.len());
+    let max_count = pixels.iter().flatten().max().copied().unwrap_or(0);
+    let max_value = max_count.clamp(1, u16::MAX as usize);
+
+    write!(writer, "P5\n{} {}\n{}\n", width, height, max_value)?;
+    // samples are one byte each up to 255, two big endian bytes after that
+    let bytes: Vec<u8> = pixels
         .iter()
-        .filter(|line| line.is_horizontal() || line.is_vertical())
-        .map(|line| line.clone())
-        .collect::<Vec<Line>>();
+        .flatten()
+        .map(|count| cmp::min(*count, max_value) as u16)
+        .flat_map(|value| {
+            if max_value < 256 {
+                vec![value as u8]
+            } else {
+                value.to_be_bytes().to_vec()
+            }
+        })
+        .collect();
+    writer.write_all(&bytes)
+}
+
+// The sweep works on the exact lines rather than on a grid, so it only ever deals with
+// coordinates derived from the end points. Everything is widened to i128 so that products of
+// coordinates can't overflow.
+type Coords = (i128, i128);
+type Direction = (i128, i128);
+
+// smallest lattice step along the line, pointing right (or up for vertical lines)
+fn direction(line: &Line) -> Direction {
+    let (dx, dy) = match line.step() {
+        // a single point, which might as well be horizontal
+        (0, 0) => (1, 0),
+        (dx, dy) => (dx as i128, dy as i128),
+    };
+
+    if dx < 0 || (dx == 0 && dy < 0) {
+        (-dx, -dy)
+    } else {
+        (dx, dy)
+    }
+}
+
+// the linear form `a * x + b * y` which is constant along lines with this direction
+fn normal((dx, dy): Direction) -> Coords {
+    (dy, -dx)
+}
+
+fn key(direction: Direction, (x, y): Coords) -> i128 {
+    let (a, b) = normal(direction);
+    a * x + b * y
+}
+
+// index of a lattice point along its line, consecutive points differ by one
+fn position((dx, dy): Direction, (x, y): Coords) -> i128 {
+    (dx * x + dy * y).div_euclid(dx * dx + dy * dy)
+}
+
+// the point where the line `key(f, p) == a` meets the line `key(g, p) == b`, if it's a lattice
+// point
+fn meet(f: Direction, a: i128, g: Direction, b: i128) -> Option<Coords> {
+    let ((fa, fb), (ga, gb)) = (normal(f), normal(g));
+    let det = fa * gb - fb * ga;
+    let (x, y) = (a * gb - fb * b, fa * b - a * ga);
+
+    (x % det == 0 && y % det == 0).then(|| (x / det, y / det))
+}
+
+#[derive(Debug, Clone)]
+struct Run {
+    from: Coords,
+    to: Coords,
+}
+
+// The segments on a single line, merged into the runs they cover and the (position) ranges
+// covered more than once.
+#[derive(Debug, Clone)]
+struct Segment {
+    from: i128,
+    to: i128,
+    start: Coords,
+    end: Coords,
+    // index of the line it came from
+    line: usize,
+}
+
+#[derive(Debug, Default)]
+struct Collinear {
+    segments: Vec<Segment>,
+    runs: Vec<Run>,
+    overlaps: Vec<(i128, i128)>,
+}
+
+impl Collinear {
+    fn merge(&mut self) {
+        let mut events: Vec<(i128, i128, Coords)> = self
+            .segments
+            .iter()
+            .flat_map(|segment| {
+                [
+                    (segment.from, 1, segment.start),
+                    (segment.to + 1, -1, segment.end),
+                ]
+            })
+            .collect();
+        events.sort_unstable();
+
+        let (mut covered, mut run_from, mut overlap_from) = (0, None, 0);
+        for group in events.chunk_by(|a, b| a.0 == b.0) {
+            let t = group[0].0;
+            let before = covered;
+            covered += group.iter().map(|(_, delta, _)| delta).sum::<i128>();
+
+            if before == 0 && covered > 0 {
+                run_from = group.iter().find(|(_, delta, _)| *delta > 0).map(|e| e.2);
+            }
+            if before > 0 && covered == 0 {
+                let to = group.iter().find(|(_, delta, _)| *delta < 0).unwrap().2;
+                self.runs.push(Run {
+                    from: run_from.unwrap(),
+                    to,
+                });
+            }
+            if before < 2 && covered >= 2 {
+                overlap_from = t;
+            }
+            if before >= 2 && covered < 2 {
+                self.overlaps.push((overlap_from, t - 1));
+            }
+        }
+    }
+
+    fn overlaps_at(&self, position: i128) -> bool {
+        let i = self.overlaps.partition_point(|(from, _)| *from <= position);
+        i > 0 && self.overlaps[i - 1].1 >= position
+    }
+}
+
+// Lines with the same direction never cross, so all crossings come from pairs of directions. For
+// a pair `f`, `g` every point is described by its keys `(key(f, p), key(g, p))`, and in those
+// coordinates the runs of `f` are horizontal and the runs of `g` vertical. Finding their crossings
+// is then the classic orthogonal segment intersection sweep, O((n + k) log n) for n runs and k
+// crossings.
+//
+// Whether two lines meet on a lattice point only depends on their keys modulo the determinant of
+// the two normals, so the active lines are kept apart by that residue and a query only walks the
+// ones it meets on the lattice. A diagonal never even looks at the anti-diagonals it crosses
+// between two points.
+fn crossings(
+    f: Direction,
+    f_lines: &HashMap<i128, Collinear>,
+    g: Direction,
+    g_lines: &HashMap<i128, Collinear>,
+    found: &mut HashMap<Coords, Vec<(Direction, i128)>>,
+) {
+    const INSERT: u8 = 0;
+    const QUERY: u8 = 1;
+    const REMOVE: u8 = 2;
 
-    count_intersections_functional(&lines)
+    let range = |d: Direction, run: &Run| {
+        let (a, b) = (key(d, run.from), key(d, run.to));
+        (cmp::min(a, b), cmp::max(a, b))
+    };
+
+    let mut events: Vec<(i128, u8, i128, i128)> = Vec::new();
+    f_lines.iter().for_each(|(a, line)| {
+        line.runs.iter().for_each(|run| {
+            let (lo, hi) = range(g, run);
+            events.push((lo, INSERT, *a, 0));
+            events.push((hi, REMOVE, *a, 0));
+        })
+    });
+    g_lines.iter().for_each(|(b, line)| {
+        line.runs.iter().for_each(|run| {
+            let (lo, hi) = range(f, run);
+            events.push((*b, QUERY, lo, hi));
+        })
+    });
+    events.sort_unstable();
+
+    let ((fa, fb), (ga, gb)) = (normal(f), normal(g));
+    let det = (fa * gb - fb * ga).abs();
+    let mut active: Vec<BTreeMap<i128, usize>> = vec![BTreeMap::new(); det as usize];
+    for (b, kind, lo, hi) in events {
+        match kind {
+            INSERT => *active[lo.rem_euclid(det) as usize].entry(lo).or_insert(0) += 1,
+            REMOVE => {
+                let residue = &mut active[lo.rem_euclid(det) as usize];
+                let count = residue.get_mut(&lo).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    residue.remove(&lo);
+                }
+            }
+            _ => (0..det)
+                .filter(|r| meet(f, *r, g, b).is_some())
+                .flat_map(|r| active[r as usize].range(lo..=hi))
+                .for_each(|(a, _)| {
+                    if let Some(p) = meet(f, *a, g, b) {
+                        let lines = found.entry(p).or_default();
+                        lines.push((f, *a));
+                        lines.push((g, b));
+                    }
+                }),
+        }
+    }
+}
+
+// groups the lines by direction and then by the line they lie on, with every group merged
+fn collinear_groups(lines: &[Line]) -> HashMap<Direction, HashMap<i128, Collinear>> {
+    let mut by_direction: HashMap<Direction, HashMap<i128, Collinear>> = HashMap::new();
+    lines.iter().enumerate().for_each(|(i, line)| {
+        let line = line.canonical();
+        let d = direction(&line);
+        let (start, end) = (
+            (line.start.x as i128, line.start.y as i128),
+            (line.end.x as i128, line.end.y as i128),
+        );
+
+        by_direction
+            .entry(d)
+            .or_default()
+            .entry(key(d, start))
+            .or_default()
+            .segments
+            .push(Segment {
+                from: position(d, start),
+                to: position(d, end),
+                start,
+                end,
+                line: i,
+            });
+    });
+    by_direction
+        .values_mut()
+        .flat_map(|lines| lines.values_mut())
+        .for_each(|line| line.merge());
+
+    by_direction
+}
+
+// The directions the sweep takes, the ones from the puzzle. Every pair of directions is swept on
+// its own, so D directions cost D * (D - 1) / 2 sweeps, which only stays cheap while D is fixed.
+// Lines at any other slope have to be counted another way.
+pub const SWEEP_DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+pub fn count_intersections_sweep(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    if let Some(line) = lines.iter().find(|line| {
+        let (dx, dy) = direction(&line.canonical());
+        !SWEEP_DIRECTIONS.contains(&(dx as isize, dy as isize))
+    }) {
+        return Err(format!(
+            "{},{} -> {},{} isn't horizontal, vertical or diagonal, which is all the sweep handles",
+            line.start.x, line.start.y, line.end.x, line.end.y
+        )
+        .into());
+    }
+
+    let by_direction = collinear_groups(lines);
+
+    // every point where lines of two different directions meet is covered at least twice
+    let mut found: HashMap<Coords, Vec<(Direction, i128)>> = HashMap::new();
+    let directions: Vec<&Direction> = by_direction.keys().collect();
+    for (i, f) in directions.iter().enumerate() {
+        for g in directions[i + 1..].iter() {
+            crossings(**f, &by_direction[*f], **g, &by_direction[*g], &mut found);
+        }
+    }
+
+    // everything else covered twice lies in the overlaps of a single line, as long as it wasn't
+    // already counted as a crossing
+    let overlapping: i128 = by_direction
+        .values()
+        .flat_map(|lines| lines.values())
+        .flat_map(|line| line.overlaps.iter())
+        .map(|(from, to)| to - from + 1)
+        .sum();
+    let counted_twice = found
+        .iter_mut()
+        .map(|(p, lines)| {
+            lines.sort_unstable();
+            lines.dedup();
+            lines
+                .iter()
+                .filter(|(d, a)| by_direction[d][a].overlaps_at(position(*d, *p)))
+                .count() as i128
+        })
+        .sum::<i128>();
+
+    Ok((found.len() as i128 + overlapping - counted_twice) as usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    pub line: Line,
+    // indices of the input lines that were merged into `line`
+    pub pieces: Vec<usize>,
+    // number of points covered by more than one of the pieces
+    pub shared: usize,
+}
+
+// Orients every line canonically and merges collinear lines that overlap or touch end to end.
+// Crossings between the merged lines are then the real ones, while `shared` counts the points
+// that were only covered twice because the same stretch got drawn more than once.
+pub fn normalize(lines: &[Line]) -> Vec<Merged> {
+    let mut merged: Vec<Merged> = collinear_groups(lines)
+        .into_iter()
+        .flat_map(|(d, lines)| lines.into_values().map(move |line| (d, line)))
+        .flat_map(|(d, line)| {
+            line.runs
+                .iter()
+                .map(|run| {
+                    let (from, to) = (position(d, run.from), position(d, run.to));
+                    let mut pieces: Vec<usize> = line
+                        .segments
+                        .iter()
+                        .filter(|segment| from <= segment.from && segment.from <= to)
+                        .map(|segment| segment.line)
+                        .collect();
+                    pieces.sort_unstable();
+                    let shared = line
+                        .overlaps
+                        .iter()
+                        .filter(|(start, _)| from <= *start && *start <= to)
+                        .map(|(start, end)| (end - start + 1) as usize)
+                        .sum();
+
+                    Merged {
+                        line: Line {
+                            start: Point::new(run.from.0 as isize, run.from.1 as isize),
+                            end: Point::new(run.to.0 as isize, run.to.1 as isize),
+                        },
+                        pieces,
+                        shared,
+                    }
+                })
+                .collect::<Vec<Merged>>()
+        })
+        .collect();
+    merged.sort_unstable_by_key(|m| m.pieces[0]);
+
+    merged
+}
+
+#[aoc(day5, part1, Imperative)]
+pub fn solver_1_imperative(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    count_intersections_imperative(
+        &filter_orientations(lines, PART_1_ORIENTATIONS),
+        MAX_SCAN_AREA,
+    )
+}
+
+#[aoc(day5, part1, Functional)]
+pub fn solver_1_functional(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    count_intersections_functional(
+        &filter_orientations(lines, PART_1_ORIENTATIONS),
+        MAX_SCAN_AREA,
+    )
 }
 
 #[aoc(day5, part2, Imperative)]
-pub fn solver_2_imperative(lines: &Vec<Line>) -> usize {
-    count_intersections_imperative(lines)
+pub fn solver_2_imperative(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    count_intersections_imperative(
+        &filter_orientations(lines, PART_2_ORIENTATIONS),
+        MAX_SCAN_AREA,
+    )
 }
 
 #[aoc(day5, part2, Functional)]
-pub fn solver_2_functional(lines: &Vec<Line>) -> usize {
-    count_intersections_functional(lines)
+pub fn solver_2_functional(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    count_intersections_functional(
+        &filter_orientations(lines, PART_2_ORIENTATIONS),
+        MAX_SCAN_AREA,
+    )
+}
+
+#[aoc(day5, part1, Rasterized)]
+pub fn solver_1_rasterized(lines: &[Line]) -> usize {
+    count_intersections_rasterized(&filter_orientations(lines, PART_1_ORIENTATIONS))
+}
+
+#[aoc(day5, part2, Rasterized)]
+pub fn solver_2_rasterized(lines: &[Line]) -> usize {
+    count_intersections_rasterized(&filter_orientations(lines, PART_2_ORIENTATIONS))
+}
+
+#[aoc(day5, part1, Sweep)]
+pub fn solver_1_sweep(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    count_intersections_sweep(&filter_orientations(lines, PART_1_ORIENTATIONS))
+}
+
+#[aoc(day5, part2, Sweep)]
+pub fn solver_2_sweep(lines: &[Line]) -> Result<usize, Box<dyn Error>> {
+    count_intersections_sweep(&filter_orientations(lines, PART_2_ORIENTATIONS))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rng::Rng;
 
     const INPUT: &str = r"0,9 -> 5,9
 8,0 -> 0,8
@@ -230,25 +1213,497 @@ mod tests {
         check("10,10 -> 0,0", "5,5");
         check("0,10 -> 10,0", "5,5");
         check("10,0 -> 0,10", "5,5");
+        check("0,0 -> 4,2", "2,1");
+        check("4,2 -> 0,0", "2,1");
+        check("7,7 -> 7,7", "7,7");
+
+        let check_not = |line, point| {
+            assert!(!Line::from_str(line)
+                .unwrap()
+                .contains(&Point::from_str(point).unwrap()));
+        };
+
+        check_not("0,0 -> 4,2", "1,0");
+        check_not("0,0 -> 4,2", "1,1");
+        check_not("0,0 -> 4,2", "6,3");
+        check_not("0,0 -> 10,10", "11,11");
+        check_not("7,7 -> 7,7", "7,8");
     }
 
     #[test]
     fn test_example_1() {
         let lines = generator(INPUT).unwrap();
-        let result_imperative = solver_1_imperative(&lines);
-        let result_functional = solver_1_functional(&lines);
+        let result_imperative = solver_1_imperative(&lines).unwrap();
+        let result_functional = solver_1_functional(&lines).unwrap();
 
         assert_eq!(result_imperative, 5);
         assert_eq!(result_functional, 5);
+        assert_eq!(solver_1_rasterized(&lines), 5);
+        assert_eq!(solver_1_sweep(&lines).unwrap(), 5);
     }
 
     #[test]
     fn test_example_2() {
         let lines = generator(INPUT).unwrap();
-        let result_imperative = solver_2_imperative(&lines);
-        let result_functional = solver_2_functional(&lines);
+        let result_imperative = solver_2_imperative(&lines).unwrap();
+        let result_functional = solver_2_functional(&lines).unwrap();
 
         assert_eq!(result_imperative, 12);
         assert_eq!(result_functional, 12);
+        assert_eq!(solver_2_rasterized(&lines), 12);
+        assert_eq!(solver_2_sweep(&lines).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_points() {
+        let points = |line: &str| {
+            Line::from_str(line)
+                .unwrap()
+                .points()
+                .map(|p| (p.x, p.y))
+                .collect::<Vec<(isize, isize)>>()
+        };
+
+        assert_eq!(points("1,1 -> 1,3"), vec![(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(points("9,7 -> 7,9"), vec![(9, 7), (8, 8), (7, 9)]);
+        assert_eq!(points("4,4 -> 4,4"), vec![(4, 4)]);
+        assert_eq!(points("0,0 -> 4,2"), vec![(0, 0), (2, 1), (4, 2)]);
+        assert_eq!(points("6,0 -> 0,9"), vec![(6, 0), (4, 3), (2, 6), (0, 9)]);
+    }
+
+    #[test]
+    fn test_orientations() {
+        let lines = generator(
+            r"0,0 -> 4,0
+3,0 -> 3,3
+0,0 -> 4,2
+1,0 -> 3,2
+4,2 -> 0,4",
+        )
+        .unwrap();
+
+        let orientations: Vec<Orientation> = lines.iter().map(|line| line.orientation()).collect();
+        assert_eq!(
+            orientations,
+            vec![
+                Orientation::Horizontal,
+                Orientation::Vertical,
+                Orientation::Sloped,
+                Orientation::Diagonal,
+                Orientation::Sloped,
+            ]
+        );
+
+        assert_eq!(solver_1_rasterized(&lines), 1);
+        assert_eq!(solver_2_rasterized(&lines), 3);
+
+        // the sloped lines only cross at lattice points, adding (0, 0), (2, 1) and (4, 2)
+        let all = filter_orientations(
+            &lines,
+            &[
+                Orientation::Horizontal,
+                Orientation::Vertical,
+                Orientation::Diagonal,
+                Orientation::Sloped,
+            ],
+        );
+        assert_eq!(
+            count_intersections_imperative(&all, MAX_SCAN_AREA).unwrap(),
+            6
+        );
+        assert_eq!(
+            count_intersections_functional(&all, MAX_SCAN_AREA).unwrap(),
+            6
+        );
+        assert_eq!(count_intersections_rasterized(&all), 6);
+        // the sweep only takes the puzzle's directions
+        assert!(count_intersections_sweep(&all).is_err());
+    }
+
+    #[test]
+    fn test_sparse_coverage() {
+        let lines = generator("0,0 -> 0,2\n-1,1 -> 1,1\n1000000,0 -> 1000000,1").unwrap();
+        let coverage = Coverage::rasterize(&lines);
+
+        assert!(matches!(coverage, Coverage::Sparse(_)));
+        assert_eq!(coverage.get(&Point { x: 0, y: 1 }), 2);
+        assert_eq!(coverage.get(&Point { x: 1000000, y: 1 }), 1);
+        assert_eq!(coverage.count_at_least(2), 1);
+        assert_eq!(coverage.count_at_least(1), 7);
+
+        let dense = Coverage::rasterize(&generator(INPUT).unwrap());
+        assert!(matches!(dense, Coverage::Dense { .. }));
+        assert_eq!(dense.get(&Point { x: 7, y: 4 }), 2);
+        assert_eq!(dense.get(&Point { x: -1, y: 4 }), 0);
+    }
+
+    #[test]
+    fn test_sweep_huge() {
+        let lines = generator(
+            r"0,0 -> 1000000000,0
+999999990,0 -> 2000000000,0
+1000000000,-1000000000 -> 1000000000,1000000000
+0,-1000000000 -> 2000000000,1000000000
+1000000000,5 -> 1000000000,5",
+        )
+        .unwrap();
+
+        // 11 points shared by the two horizontal lines, one of which is also crossed by the
+        // vertical and the diagonal line, plus the single point sitting on the vertical line
+        assert_eq!(count_intersections_sweep(&lines).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_sweep_matches_rasterized() {
+        let mut rng = Rng::new(5);
+        for _ in 0..50 {
+            let lines: Vec<Line> = (0..12)
+                .map(|_| {
+                    let start = Point {
+                        x: rng.below(20) as isize - 10,
+                        y: rng.below(20) as isize - 10,
+                    };
+                    let length = rng.below(8) as isize;
+                    let (dx, dy) = SWEEP_DIRECTIONS[rng.below(4)];
+                    let end = Point {
+                        x: start.x + dx * length,
+                        y: start.y + dy * length,
+                    };
+                    if rng.below(2) == 0 {
+                        Line { start, end }
+                    } else {
+                        Line {
+                            start: end,
+                            end: start,
+                        }
+                    }
+                })
+                .collect();
+
+            assert_eq!(
+                count_intersections_sweep(&lines).unwrap(),
+                count_intersections_rasterized(&lines)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sweep_off_lattice() {
+        // every diagonal crosses every anti-diagonal, but always between two lattice points
+        let lines: Vec<Line> = (0..200)
+            .flat_map(|i| {
+                [
+                    Line {
+                        start: Point::new(2 * i, 0),
+                        end: Point::new(2 * i + 1000, 1000),
+                    },
+                    Line {
+                        start: Point::new(2 * i + 1, 1000),
+                        end: Point::new(2 * i + 1001, 0),
+                    },
+                ]
+            })
+            .collect();
+
+        assert_eq!(count_intersections_sweep(&lines).unwrap(), 0);
+        assert_eq!(count_intersections_rasterized(&lines), 0);
+    }
+
+    #[test]
+    fn test_negative_coordinates() {
+        let lines = generator(
+            r"-5,-5 -> -1,-1
+-5,-1 -> -1,-5
+-3,-6 -> -3,-2
+-10,-10 -> -10,-10",
+        )
+        .unwrap();
+
+        assert_eq!(
+            count_intersections_imperative(&lines, MAX_SCAN_AREA).unwrap(),
+            1
+        );
+        assert_eq!(
+            count_intersections_functional(&lines, MAX_SCAN_AREA).unwrap(),
+            1
+        );
+        assert_eq!(count_intersections_rasterized(&lines), 1);
+        assert_eq!(count_intersections_sweep(&lines).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_scan_region_too_large() {
+        let lines = generator("-1000000,0 -> 1000000,0\n0,-1000000 -> 0,1000000").unwrap();
+
+        assert!(count_intersections_imperative(&lines, MAX_SCAN_AREA).is_err());
+        assert!(count_intersections_functional(&lines, MAX_SCAN_AREA).is_err());
+        assert_eq!(count_intersections_sweep(&lines).unwrap(), 1);
+
+        let lines = generator("-100,0 -> 100,0\n0,-100 -> 0,100").unwrap();
+        assert!(count_intersections_imperative(&lines, 201 * 201 - 1).is_err());
+        assert_eq!(
+            count_intersections_imperative(&lines, 201 * 201).unwrap(),
+            1
+        );
+        assert_eq!(count_intersections_functional(&[], 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let lines = generator(INPUT).unwrap();
+
+        assert_eq!(
+            render_ascii(
+                &filter_orientations(&lines, PART_1_ORIENTATIONS),
+                &RenderOptions::default()
+            )
+            .unwrap(),
+            r".......1..
+..1....1..
+..1....1..
+.......1..
+.112111211
+..........
+..........
+..........
+..........
+222111...."
+        );
+        assert_eq!(
+            render_ascii(&lines, &RenderOptions::default()).unwrap(),
+            r"1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111...."
+        );
+
+        let options = RenderOptions {
+            crop: Some((Point::new(0, 7), Point::new(4, 9))),
+            scale: 2,
+        };
+        assert_eq!(render_ascii(&lines, &options).unwrap(), "1..\n221");
+    }
+
+    #[test]
+    fn test_points_within() {
+        let mut rng = Rng::new(36);
+        let mut coordinate = || rng.below(21) as isize - 10;
+        for _ in 0..500 {
+            let line = Line {
+                start: Point::new(coordinate(), coordinate()),
+                end: Point::new(coordinate(), coordinate()),
+            };
+            let (min, max) = (
+                Point::new(coordinate(), coordinate()),
+                Point::new(coordinate(), coordinate()),
+            );
+
+            let inside: Vec<Point> = line
+                .points()
+                .filter(|p| min.x <= p.x && p.x <= max.x && min.y <= p.y && p.y <= max.y)
+                .collect();
+            assert_eq!(line.points_within(&min, &max).collect::<Vec<_>>(), inside);
+        }
+    }
+
+    #[test]
+    fn test_render_huge_survey() {
+        let lines = generator(
+            "0,0 -> 1000000000,1000000000\n1000000000,0 -> 0,1000000000\n0,500000000 -> 1000000000,500000000",
+        )
+        .unwrap();
+
+        // the whole survey is far too many pixels, even scaled down a lot
+        assert!(render_ascii(&lines, &RenderOptions::default()).is_err());
+        let options = RenderOptions {
+            crop: None,
+            scale: 1000,
+        };
+        assert!(render_ascii(&lines, &options).is_err());
+        let mut pgm = Vec::new();
+        assert!(write_pgm(&lines, &options, &mut pgm).is_err());
+
+        // but a small window around the middle is quick to draw
+        let options = RenderOptions {
+            crop: Some((
+                Point::new(499999998, 499999999),
+                Point::new(500000002, 500000001),
+            )),
+            scale: 1,
+        };
+        assert_eq!(
+            render_ascii(&lines, &options).unwrap(),
+            ".1.1.\n11311\n.1.1."
+        );
+
+        // scaling a large survey down only costs the points on its lines
+        let lines =
+            generator("0,0 -> 100000,100000\n100000,0 -> 0,100000\n0,50000 -> 100000,50000")
+                .unwrap();
+        let options = RenderOptions {
+            crop: None,
+            scale: 1000,
+        };
+        let rendered = render_ascii(&lines, &options).unwrap();
+        assert_eq!(rendered.lines().count(), 101);
+        assert_eq!(rendered.lines().nth(50).unwrap().len(), 101);
+        assert_eq!(rendered.lines().nth(50).unwrap().chars().nth(50), Some('3'));
+    }
+
+    #[test]
+    fn test_write_pgm() {
+        let lines = generator("-1,0 -> 1,0\n0,-1 -> 0,1").unwrap();
+        let mut pgm = Vec::new();
+        write_pgm(&lines, &RenderOptions::default(), &mut pgm).unwrap();
+
+        let mut expected = b"P5\n3 3\n2\n".to_vec();
+        expected.extend([0, 1, 0, 1, 2, 1, 0, 1, 0]);
+        assert_eq!(pgm, expected);
+    }
+
+    #[test]
+    fn test_vent_index() {
+        let lines = generator(INPUT).unwrap();
+        let index = VentIndex::new(&lines);
+
+        assert_eq!(index.points_covered_at_least(2).len(), 12);
+        assert_eq!(
+            index.points_covered_at_least(3),
+            vec![Point::new(4, 4), Point::new(6, 4)]
+        );
+        assert_eq!(index.lines_at(&Point::new(4, 4)), &[1, 2, 8]);
+        assert_eq!(index.lines_at(&Point::new(0, 9)), &[0, 6]);
+        assert!(index.lines_at(&Point::new(9, 9)).is_empty());
+
+        // 0,9 -> 5,9 only runs into the line drawn over it
+        assert_eq!(index.overlapping(0), vec![6]);
+        // 9,4 -> 3,4 is crossed by almost everything
+        assert_eq!(index.overlapping(2), vec![1, 4, 5, 7, 8, 9]);
+        assert_eq!(
+            index.lines_overlapping(&Line::from_str("0,0 -> 0,9").unwrap()),
+            vec![0, 1, 6, 8]
+        );
+        assert_eq!(index.line(6).start, Point::new(0, 9));
+    }
+
+    #[test]
+    fn test_canonical() {
+        let canonical = |line: &str| Line::from_str(line).unwrap().canonical();
+
+        assert_eq!(canonical("5,9 -> 0,9"), canonical("0,9 -> 5,9"));
+        assert_eq!(canonical("2,2 -> 2,1").start, Point::new(2, 1));
+        assert_eq!(canonical("8,0 -> 0,8").start, Point::new(0, 8));
+        assert_eq!(canonical("0,0 -> 8,8").start, Point::new(0, 0));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let lines = generator(INPUT).unwrap();
+        let merged = normalize(&lines);
+
+        assert_eq!(merged.len(), 8);
+        assert_eq!(
+            merged[0],
+            Merged {
+                line: Line::from_str("0,9 -> 5,9").unwrap(),
+                pieces: vec![0, 6],
+                shared: 3,
+            }
+        );
+        assert_eq!(
+            merged[2],
+            Merged {
+                line: Line::from_str("1,4 -> 9,4").unwrap(),
+                pieces: vec![2, 7],
+                shared: 1,
+            }
+        );
+
+        // 3 + 1 of the 12 points only come from lines drawn twice, the other 8 are real crossings
+        let merged_lines: Vec<Line> = merged.iter().map(|m| m.line.clone()).collect();
+        assert_eq!(merged.iter().map(|m| m.shared).sum::<usize>(), 4);
+        assert_eq!(count_intersections_sweep(&merged_lines).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_normalize_adjacent() {
+        let lines = generator("0,0 -> 2,2\n6,6 -> 3,3\n8,8 -> 9,9\n4,4 -> 5,5").unwrap();
+
+        assert_eq!(
+            normalize(&lines),
+            vec![
+                Merged {
+                    line: Line::from_str("0,0 -> 6,6").unwrap(),
+                    pieces: vec![0, 1, 3],
+                    shared: 2,
+                },
+                Merged {
+                    line: Line::from_str("8,8 -> 9,9").unwrap(),
+                    pieces: vec![2],
+                    shared: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wkt_round_trip() {
+        let lines = generator(INPUT).unwrap();
+        let line = &lines[1];
+
+        assert_eq!(line.to_wkt(), "LINESTRING(8 0, 0 8)");
+        assert_eq!(&Line::from_wkt(&line.to_wkt()).unwrap(), line);
+        assert_eq!(
+            Line::from_wkt(" linestring ( -1 2 ,3.0 -4 ) ").unwrap(),
+            Line::from_str("-1,2 -> 3,-4").unwrap()
+        );
+
+        let wkt = lines_to_wkt(&lines);
+        assert!(wkt.starts_with("MULTILINESTRING((0 9, 5 9), (8 0, 0 8), "));
+        assert_eq!(lines_from_wkt(&wkt).unwrap(), lines);
+        assert_eq!(generator(&wkt).unwrap(), lines);
+
+        let p = Point::new(-3, 7);
+        assert_eq!(p.to_wkt(), "POINT(-3 7)");
+        assert_eq!(Point::from_wkt("POINT (-3 7)").unwrap(), p);
+    }
+
+    #[test]
+    fn test_wkt_multi() {
+        let lines = lines_from_wkt(
+            r"LINESTRING(0 0, 4 0, 4 4)
+MULTILINESTRING((1 1, 2 2), (5 5, 6 5))
+MULTILINESTRING EMPTY",
+        )
+        .unwrap();
+        assert_eq!(
+            lines,
+            generator("0,0 -> 4,0\n4,0 -> 4,4\n1,1 -> 2,2\n5,5 -> 6,5").unwrap()
+        );
+        assert_eq!(lines_to_wkt(&[]), "MULTILINESTRING EMPTY");
+
+        let overlaps = VentIndex::new(&generator(INPUT).unwrap()).points_covered_at_least(3);
+        let wkt = points_to_wkt(&overlaps);
+        assert_eq!(wkt, "MULTIPOINT((4 4), (6 4))");
+        assert_eq!(points_from_wkt(&wkt).unwrap(), overlaps);
+        assert_eq!(points_from_wkt("MULTIPOINT(4 4, 6 4)").unwrap(), overlaps);
+        assert_eq!(points_to_wkt(&[]), "MULTIPOINT EMPTY");
+    }
+
+    #[test]
+    fn test_wkt_errors() {
+        assert!(Line::from_wkt("LINESTRING(0 0, 1 1, 2 2)").is_err());
+        assert!(Line::from_wkt("LINESTRING(0 0, 1 1) trailing").is_err());
+        assert!(Line::from_wkt("LINESTRING(0 0, 1.5 1)").is_err());
+        assert!(Line::from_wkt("POINT(0 0)").is_err());
+        assert!(lines_from_wkt("LINESTRING(0 0)").is_err());
+        assert!(lines_from_wkt("LINESTRING(0 0, 1 1").is_err());
+        assert!(Point::from_wkt("POINT(1)").is_err());
     }
 }
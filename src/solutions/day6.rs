@@ -1,6 +1,36 @@
-use std::collections::HashMap;
+use std::{cmp, collections::HashMap, io};
 
 use aoc_runner_derive::{aoc, aoc_generator};
+use num_bigint::BigUint;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lifecycle {
+    // timer a fish goes back to after giving birth
+    pub reset: usize,
+    // timer of a newborn's own cycle, once it has matured
+    pub newborn: usize,
+    // days a newborn takes to mature, which are added to its first cycle
+    pub maturation_delay: usize,
+}
+
+impl Lifecycle {
+    // the timer a newborn starts at, 8 for the puzzle's fish
+    fn birth_timer(&self) -> usize {
+        self.newborn + self.maturation_delay
+    }
+
+    fn max_timer(&self) -> usize {
+        cmp::max(self.reset, self.birth_timer())
+    }
+}
+
+pub const PUZZLE: Lifecycle = Lifecycle {
+    reset: 6,
+    newborn: 6,
+    maturation_delay: 2,
+};
+pub const PART_1_DAYS: usize = 80;
+pub const PART_2_DAYS: usize = 256;
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct LanternFish {
@@ -9,14 +39,20 @@ pub struct LanternFish {
 
 impl Default for LanternFish {
     fn default() -> Self {
-        LanternFish { days: 8 }
+        LanternFish::newborn(&PUZZLE)
     }
 }
 
 impl LanternFish {
-    fn step(&mut self) -> bool {
+    pub fn newborn(lifecycle: &Lifecycle) -> Self {
+        LanternFish {
+            days: lifecycle.birth_timer(),
+        }
+    }
+
+    fn step(&mut self, lifecycle: &Lifecycle) -> bool {
         if self.days == 0 {
-            self.days = 6;
+            self.days = lifecycle.reset;
             return true;
         }
         self.days -= 1;
@@ -24,9 +60,9 @@ impl LanternFish {
         return false;
     }
 
-    fn check_step(&self) -> (usize, bool) {
+    fn check_step(&self, lifecycle: &Lifecycle) -> (usize, bool) {
         if self.days == 0 {
-            return (6, true);
+            return (lifecycle.reset, true);
         }
         return (self.days - 1, false);
     }
@@ -42,48 +78,39 @@ pub fn generator(input: &str) -> Vec<LanternFish> {
         .collect()
 }
 
-#[aoc(day6, part1)]
-pub fn solver_1(input: &Vec<LanternFish>) -> usize {
-    let mut lantern_fish = input.clone();
-
-    (1..=80).for_each(|_| {
-        let new_fish = lantern_fish
-            .iter_mut()
-            .map(|lf| lf.step()) // mutating map bad!
-            .filter(|nf| *nf)
-            .count();
-
-        lantern_fish.extend((0..new_fish).map(|_| LanternFish::default()));
-    });
-
-    lantern_fish.len()
-}
-
-#[aoc(day6, part2)]
-pub fn solver_2(input: &Vec<LanternFish>) -> usize {
-    let mut fish_count: HashMap<LanternFish, usize> = (0..=8)
-        .map(|days| LanternFish { days })
-        .map(|lf| (lf, 0))
-        .collect();
+// number of fish after `days`, keeping track of how many fish there are per timer value
+pub fn simulate(input: &[LanternFish], lifecycle: &Lifecycle, days: usize) -> usize {
+    let max_timer = input
+        .iter()
+        .map(|lf| lf.days)
+        .chain([lifecycle.max_timer()])
+        .max()
+        .unwrap();
+    let empty = || -> HashMap<LanternFish, usize> {
+        (0..=max_timer)
+            .map(|days| LanternFish { days })
+            .map(|lf| (lf, 0))
+            .collect()
+    };
 
+    let mut fish_count = empty();
     input
         .iter()
         .for_each(|lf| *fish_count.get_mut(lf).unwrap() += 1);
 
-    (1..=256).for_each(|_| {
-        let mut new_fish_count: HashMap<LanternFish, usize> = (0..=8)
-            .map(|days| LanternFish { days })
-            .map(|lf| (lf, 0))
-            .collect();
+    (1..=days).for_each(|_| {
+        let mut new_fish_count = empty();
 
         fish_count.iter().for_each(|(lf, cnt)| {
-            let (new_days, if_reproduce) = lf.check_step();
+            let (new_days, if_reproduce) = lf.check_step(lifecycle);
             *new_fish_count
                 .get_mut(&LanternFish { days: new_days })
                 .unwrap() += *cnt;
 
             if if_reproduce {
-                *new_fish_count.get_mut(&LanternFish { days: 8 }).unwrap() += *cnt;
+                *new_fish_count
+                    .get_mut(&LanternFish::newborn(lifecycle))
+                    .unwrap() += *cnt;
             }
         });
 
@@ -93,38 +120,456 @@ pub fn solver_2(input: &Vec<LanternFish>) -> usize {
     fish_count.values().sum()
 }
 
-#[aoc(day6, part1, Faster)]
-pub fn solver_1_faster(input: &Vec<LanternFish>) -> usize {
-    let mut fish_count: HashMap<LanternFish, usize> = (0..=8)
-        .map(|days| LanternFish { days })
-        .map(|lf| (lf, 0))
-        .collect();
+// Yields how many fish have each timer value, starting with the input on day 0 and stopping once
+// the counts no longer fit in a u128.
+#[derive(Clone, Debug)]
+pub struct History {
+    lifecycle: Lifecycle,
+    counts: Option<Vec<u128>>,
+}
 
-    input
+impl Iterator for History {
+    type Item = Vec<u128>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let counts = self.counts.take()?;
+
+        let spawning = counts[0];
+        let mut next = counts[1..].to_vec();
+        next.push(0);
+        let reset = next[self.lifecycle.reset].checked_add(spawning);
+        let next = reset.and_then(|reset| {
+            next[self.lifecycle.reset] = reset;
+            let birth = self.lifecycle.birth_timer();
+            next[birth] = next[birth].checked_add(spawning)?;
+            // the total has to fit as well
+            next.iter().try_fold(0u128, |acc, c| acc.checked_add(*c))?;
+
+            Some(next)
+        });
+        self.counts = next;
+
+        Some(counts)
+    }
+}
+
+pub fn history(input: &[LanternFish], lifecycle: &Lifecycle) -> History {
+    let n = input
         .iter()
-        .for_each(|lf| *fish_count.get_mut(lf).unwrap() += 1);
+        .map(|lf| lf.days)
+        .chain([lifecycle.max_timer()])
+        .max()
+        .unwrap()
+        + 1;
+    let mut counts = vec![0; n];
+    input.iter().for_each(|lf| counts[lf.days] += 1);
+
+    History {
+        lifecycle: lifecycle.clone(),
+        counts: Some(counts),
+    }
+}
 
-    (1..=80).for_each(|_| {
-        let mut new_fish_count: HashMap<LanternFish, usize> = (0..=8)
-            .map(|days| LanternFish { days })
-            .map(|lf| (lf, 0))
-            .collect();
+// one row per day: the day, the number of fish per timer value and the total
+pub fn write_history_csv<W: io::Write>(
+    input: &[LanternFish],
+    lifecycle: &Lifecycle,
+    days: usize,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut history = history(input, lifecycle).take(days + 1).peekable();
+    let timers = history.peek().map_or(0, |counts| counts.len());
+
+    let header: Vec<String> = (0..timers).map(|t| format!("timer_{}", t)).collect();
+    writeln!(writer, "day,{},total", header.join(","))?;
+    for (day, counts) in history.enumerate() {
+        let total: u128 = counts.iter().sum();
+        let counts: Vec<String> = counts.iter().map(|c| c.to_string()).collect();
+        writeln!(writer, "{},{},{}", day, counts.join(","), total)?;
+    }
 
-        fish_count.iter().for_each(|(lf, cnt)| {
-            let (new_days, if_reproduce) = lf.check_step();
-            *new_fish_count
-                .get_mut(&LanternFish { days: new_days })
-                .unwrap() += *cnt;
+    Ok(())
+}
 
-            if if_reproduce {
-                *new_fish_count.get_mut(&LanternFish { days: 8 }).unwrap() += *cnt;
+// `None` if there are no fish, or the population outgrows a u128 before getting there
+pub fn first_day_exceeding(
+    input: &[LanternFish],
+    lifecycle: &Lifecycle,
+    threshold: u128,
+) -> Option<usize> {
+    if input.is_empty() {
+        return None;
+    }
+
+    history(input, lifecycle).position(|counts| counts.iter().sum::<u128>() > threshold)
+}
+
+const PRIME: u64 = 1_000_000_007;
+
+fn pow_mod(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut result = 1;
+    base %= modulus;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = (result as u128 * base as u128 % modulus as u128) as u64;
+        }
+        base = (base as u128 * base as u128 % modulus as u128) as u64;
+        exp >>= 1;
+    }
+
+    result
+}
+
+// Berlekamp-Massey over the integers modulo `PRIME`: the shortest `c` with
+// `s[n] = c[0] * s[n - 1] + ... + c[l - 1] * s[n - l]` for every `n >= l`
+fn berlekamp_massey(s: &[u64]) -> Vec<u64> {
+    let p = PRIME;
+    let (mut c, mut b) = (vec![1u64], vec![1u64]);
+    let (mut l, mut m, mut last) = (0, 1, 1u64);
+
+    for n in 0..s.len() {
+        let d = (1..=l).fold(s[n] % p, |acc, i| {
+            (acc + c.get(i).copied().unwrap_or(0) * s[n - i]) % p
+        });
+        if d == 0 {
+            m += 1;
+            continue;
+        }
+
+        let coef = d * pow_mod(last, p - 2, p) % p;
+        let previous = c.clone();
+        c.resize(cmp::max(c.len(), b.len() + m), 0);
+        b.iter()
+            .enumerate()
+            .for_each(|(i, bi)| c[i + m] = (c[i + m] + p - coef * bi % p) % p);
+
+        if 2 * l <= n {
+            l = n + 1 - l;
+            b = previous;
+            last = d;
+            m = 1;
+        } else {
+            m += 1;
+        }
+    }
+
+    c.resize(l + 1, 0);
+    c[1..].iter().map(|ci| (p - ci) % p).collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    // population(n) = coefficients[0] * population(n - 1) + ... + coefficients[k - 1] * population(n - k)
+    pub coefficients: Vec<i64>,
+}
+
+impl Recurrence {
+    pub fn order(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    // x^k - c[0] * x^(k - 1) - ... - c[k - 1], highest power first
+    pub fn characteristic_polynomial(&self) -> Vec<i64> {
+        [1].into_iter()
+            .chain(self.coefficients.iter().map(|c| -c))
+            .collect()
+    }
+
+    // The factor the population grows by each day in the long run. That's the largest root of the
+    // characteristic polynomial, which for non-negative coefficients is the only positive one, so
+    // it can be found by bisection.
+    pub fn growth_rate(&self) -> f64 {
+        let polynomial = self.characteristic_polynomial();
+        let at = |x: f64| polynomial.iter().fold(0.0, |acc, c| acc * x + *c as f64);
+
+        let (mut low, mut high) = (
+            0.0,
+            1.0 + self.coefficients.iter().map(|c| c.abs()).max().unwrap_or(0) as f64,
+        );
+        (0..200).for_each(|_| {
+            let mid = (low + high) / 2.0;
+            if at(mid) > 0.0 {
+                high = mid;
+            } else {
+                low = mid;
             }
         });
 
-        fish_count = new_fish_count;
+        high
+    }
+
+    // Continues `initial`, which needs at least `order` terms, up to `n` terms in total. The
+    // positive and negative parts are summed separately, so this is `None` once a term doesn't fit
+    // in a u128 or would come out negative.
+    pub fn extend(&self, initial: &[u128], n: usize) -> Option<Vec<u128>> {
+        let mut terms = initial.to_vec();
+        while terms.len() < n {
+            let (mut positive, mut negative) = (0u128, 0u128);
+            for (i, c) in self.coefficients.iter().enumerate() {
+                let part = (c.unsigned_abs() as u128).checked_mul(terms[terms.len() - 1 - i])?;
+                if *c > 0 {
+                    positive = positive.checked_add(part)?;
+                } else {
+                    negative = negative.checked_add(part)?;
+                }
+            }
+            terms.push(positive.checked_sub(negative)?);
+        }
+
+        Some(terms)
+    }
+
+    // whether the recurrence reproduces `days` days of simulating the fish
+    pub fn verify(&self, input: &[LanternFish], lifecycle: &Lifecycle, days: usize) -> bool {
+        let simulated: Vec<u128> = history(input, lifecycle)
+            .take(days + 1)
+            .map(|counts| counts.iter().sum())
+            .collect();
+
+        simulated.len() == days + 1
+            && self.extend(
+                &simulated[..cmp::min(self.order(), simulated.len())],
+                days + 1,
+            ) == Some(simulated)
+    }
+}
+
+// Recovers the recurrence of the population from a few dozen days of simulation. Its order can't
+// be more than the number of timer values, so twice that many days (with some to spare) pin it
+// down, and the coefficients are small enough to read back from their values modulo `PRIME`.
+pub fn derive_recurrence(input: &[LanternFish], lifecycle: &Lifecycle) -> Recurrence {
+    let timers = history(input, lifecycle).next().unwrap().len();
+    let totals: Vec<u64> = history(input, lifecycle)
+        .take(2 * timers + 8)
+        .map(|counts| (counts.iter().sum::<u128>() % PRIME as u128) as u64)
+        .collect();
+
+    let coefficients = berlekamp_massey(&totals)
+        .into_iter()
+        .map(|c| {
+            if c > PRIME / 2 {
+                c as i64 - PRIME as i64
+            } else {
+                c as i64
+            }
+        })
+        .collect();
+
+    Recurrence { coefficients }
+}
+
+// The arithmetic the population counts are done in, so the same matrix code can count exactly,
+// with arbitrary precision or modulo some number.
+trait Counting {
+    type Count: Clone;
+
+    fn zero(&self) -> Self::Count;
+    fn one(&self) -> Self::Count;
+    fn add(&self, a: &Self::Count, b: &Self::Count) -> Self::Count;
+    fn mul(&self, a: &Self::Count, b: &Self::Count) -> Self::Count;
+}
+
+// exact, or `None` once it doesn't fit
+struct Checked;
+
+impl Counting for Checked {
+    type Count = Option<u128>;
+
+    fn zero(&self) -> Self::Count {
+        Some(0)
+    }
+
+    fn one(&self) -> Self::Count {
+        Some(1)
+    }
+
+    fn add(&self, a: &Self::Count, b: &Self::Count) -> Self::Count {
+        a.and_then(|a| b.and_then(|b| a.checked_add(b)))
+    }
+
+    fn mul(&self, a: &Self::Count, b: &Self::Count) -> Self::Count {
+        a.and_then(|a| b.and_then(|b| a.checked_mul(b)))
+    }
+}
+
+struct Big;
+
+impl Counting for Big {
+    type Count = BigUint;
+
+    fn zero(&self) -> Self::Count {
+        BigUint::from(0u8)
+    }
+
+    fn one(&self) -> Self::Count {
+        BigUint::from(1u8)
+    }
+
+    fn add(&self, a: &Self::Count, b: &Self::Count) -> Self::Count {
+        a + b
+    }
+
+    fn mul(&self, a: &Self::Count, b: &Self::Count) -> Self::Count {
+        a * b
+    }
+}
+
+struct Modular(u64);
+
+impl Counting for Modular {
+    type Count = u64;
+
+    fn zero(&self) -> Self::Count {
+        0
+    }
+
+    fn one(&self) -> Self::Count {
+        1 % self.0
+    }
+
+    fn add(&self, a: &Self::Count, b: &Self::Count) -> Self::Count {
+        ((*a as u128 + *b as u128) % self.0 as u128) as u64
+    }
+
+    fn mul(&self, a: &Self::Count, b: &Self::Count) -> Self::Count {
+        ((*a as u128 * *b as u128) % self.0 as u128) as u64
+    }
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+fn mat_mul<C: Counting>(c: &C, a: &Matrix<C::Count>, b: &Matrix<C::Count>) -> Matrix<C::Count> {
+    let n = a.len();
+    (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| (0..n).fold(c.zero(), |acc, k| c.add(&acc, &c.mul(&a[i][k], &b[k][j]))))
+                .collect()
+        })
+        .collect()
+}
+
+// Counts of fish per timer value after `days`. A day is a linear map on the counts, so `days` of
+// them are the transition matrix raised to the power `days`, done by repeated squaring.
+fn timer_counts<C: Counting>(
+    c: &C,
+    input: &[LanternFish],
+    lifecycle: &Lifecycle,
+    days: u64,
+) -> Vec<C::Count> {
+    let n = input
+        .iter()
+        .map(|lf| lf.days)
+        .chain([lifecycle.max_timer()])
+        .max()
+        .unwrap()
+        + 1;
+
+    // counts[to] += transition[to][from] * counts[from]
+    let mut transition: Matrix<C::Count> = vec![vec![c.zero(); n]; n];
+    (1..n).for_each(|from| transition[from - 1][from] = c.one());
+    transition[lifecycle.reset][0] = c.add(&transition[lifecycle.reset][0], &c.one());
+    let birth = lifecycle.birth_timer();
+    transition[birth][0] = c.add(&transition[birth][0], &c.one());
+
+    let mut power: Matrix<C::Count> = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| if i == j { c.one() } else { c.zero() })
+                .collect()
+        })
+        .collect();
+    let mut days = days;
+    while days > 0 {
+        if days & 1 == 1 {
+            power = mat_mul(c, &power, &transition);
+        }
+        days >>= 1;
+        if days > 0 {
+            transition = mat_mul(c, &transition, &transition);
+        }
+    }
+
+    let mut counts = vec![c.zero(); n];
+    input
+        .iter()
+        .for_each(|lf| counts[lf.days] = c.add(&counts[lf.days], &c.one()));
+
+    (0..n)
+        .map(|to| {
+            (0..n).fold(c.zero(), |acc, from| {
+                c.add(&acc, &c.mul(&power[to][from], &counts[from]))
+            })
+        })
+        .collect()
+}
+
+fn population<C: Counting>(
+    c: &C,
+    input: &[LanternFish],
+    lifecycle: &Lifecycle,
+    days: u64,
+) -> C::Count {
+    timer_counts(c, input, lifecycle, days)
+        .iter()
+        .fold(c.zero(), |acc, count| c.add(&acc, count))
+}
+
+// `None` if the population, or anything along the way to it, doesn't fit in a u128
+pub fn population_u128(input: &[LanternFish], lifecycle: &Lifecycle, days: u64) -> Option<u128> {
+    population(&Checked, input, lifecycle, days)
+}
+
+pub fn population_big(input: &[LanternFish], lifecycle: &Lifecycle, days: u64) -> BigUint {
+    population(&Big, input, lifecycle, days)
+}
+
+pub fn population_mod(
+    input: &[LanternFish],
+    lifecycle: &Lifecycle,
+    days: u64,
+    modulus: u64,
+) -> u64 {
+    assert!(modulus > 0, "modulus must be positive");
+
+    population(&Modular(modulus), input, lifecycle, days)
+}
+
+#[aoc(day6, part1)]
+pub fn solver_1(input: &Vec<LanternFish>) -> usize {
+    let mut lantern_fish = input.clone();
+
+    (1..=PART_1_DAYS).for_each(|_| {
+        let new_fish = lantern_fish
+            .iter_mut()
+            .map(|lf| lf.step(&PUZZLE)) // mutating map bad!
+            .filter(|nf| *nf)
+            .count();
+
+        lantern_fish.extend((0..new_fish).map(|_| LanternFish::default()));
     });
 
-    fish_count.values().sum()
+    lantern_fish.len()
+}
+
+#[aoc(day6, part2)]
+pub fn solver_2(input: &[LanternFish]) -> usize {
+    simulate(input, &PUZZLE, PART_2_DAYS)
+}
+
+#[aoc(day6, part1, Faster)]
+pub fn solver_1_faster(input: &[LanternFish]) -> usize {
+    simulate(input, &PUZZLE, PART_1_DAYS)
+}
+
+#[aoc(day6, part1, Matrix)]
+pub fn solver_1_matrix(input: &[LanternFish]) -> u128 {
+    population_u128(input, &PUZZLE, PART_1_DAYS as u64).unwrap()
+}
+
+#[aoc(day6, part2, Matrix)]
+pub fn solver_2_matrix(input: &[LanternFish]) -> u128 {
+    population_u128(input, &PUZZLE, PART_2_DAYS as u64).unwrap()
 }
 
 #[cfg(test)]
@@ -156,4 +601,228 @@ mod tests {
 
         assert_eq!(result, 26984457539);
     }
+
+    #[test]
+    fn example_days() {
+        let input = generator(INPUT);
+
+        assert_eq!(simulate(&input, &PUZZLE, 0), 5);
+        assert_eq!(simulate(&input, &PUZZLE, 18), 26);
+    }
+
+    #[test]
+    fn custom_lifecycle() {
+        // splits every other day and newborns join in straight away
+        let binary = Lifecycle {
+            reset: 1,
+            newborn: 1,
+            maturation_delay: 0,
+        };
+        let input = vec![LanternFish { days: 1 }];
+        assert_eq!(simulate(&input, &binary, 10), 32);
+
+        // a slower species, checked against stepping every fish
+        let slow = Lifecycle {
+            reset: 9,
+            newborn: 4,
+            maturation_delay: 3,
+        };
+        let input = generator(INPUT);
+        let mut fish = input.clone();
+        (0..60).for_each(|_| {
+            let born = fish
+                .iter_mut()
+                .map(|lf| lf.step(&slow))
+                .filter(|nf| *nf)
+                .count();
+            fish.extend((0..born).map(|_| LanternFish::newborn(&slow)));
+        });
+        assert_eq!(simulate(&input, &slow, 60), fish.len());
+    }
+
+    #[test]
+    fn example_matrix() {
+        let input = generator(INPUT);
+
+        assert_eq!(solver_1_matrix(&input), 5934);
+        assert_eq!(solver_2_matrix(&input), 26984457539);
+        assert_eq!(population_u128(&input, &PUZZLE, 0), Some(5));
+    }
+
+    #[test]
+    fn matrix_matches_simulation() {
+        let input = generator(INPUT);
+        let slow = Lifecycle {
+            reset: 9,
+            newborn: 4,
+            maturation_delay: 3,
+        };
+
+        for days in [1, 17, 100, 300] {
+            assert_eq!(
+                population_u128(&input, &slow, days),
+                Some(simulate(&input, &slow, days as usize) as u128)
+            );
+        }
+    }
+
+    #[test]
+    fn matrix_large_days() {
+        let input = generator(INPUT);
+        let p = 1_000_000_007;
+
+        // a u128 runs out a little before 1000 days
+        assert!(population_u128(&input, &PUZZLE, 900).is_some());
+        assert!(population_u128(&input, &PUZZLE, 2000).is_none());
+
+        let big = population_big(&input, &PUZZLE, 2000);
+        assert_eq!(
+            big.clone() % p,
+            BigUint::from(population_mod(&input, &PUZZLE, 2000, p))
+        );
+        assert_eq!(
+            population_big(&input, &PUZZLE, 256),
+            BigUint::from(26984457539u64)
+        );
+
+        // too many days to check directly, but the answer has to agree with the ones modulo the
+        // factors of the modulus
+        let days = 1_000_000_000_000_000_000;
+        let full = population_mod(&input, &PUZZLE, days, p * 3);
+        assert_eq!(full % p, population_mod(&input, &PUZZLE, days, p));
+        assert_eq!(full % 3, population_mod(&input, &PUZZLE, days, 3));
+    }
+
+    #[test]
+    fn example_history() {
+        let input = generator(INPUT);
+        let days: Vec<Vec<u128>> = history(&input, &PUZZLE).take(4).collect();
+
+        assert_eq!(
+            days,
+            vec![
+                vec![0, 1, 1, 2, 1, 0, 0, 0, 0],
+                vec![1, 1, 2, 1, 0, 0, 0, 0, 0],
+                vec![1, 2, 1, 0, 0, 0, 1, 0, 1],
+                vec![2, 1, 0, 0, 0, 1, 1, 1, 1],
+            ]
+        );
+        assert_eq!(
+            history(&input, &PUZZLE)
+                .nth(256)
+                .unwrap()
+                .iter()
+                .sum::<u128>(),
+            26984457539
+        );
+        // the iterator stops instead of overflowing
+        assert!(history(&input, &PUZZLE).count() < 1000);
+    }
+
+    #[test]
+    fn example_history_csv() {
+        let input = generator(INPUT);
+        let mut csv = Vec::new();
+        write_history_csv(&input, &PUZZLE, 2, &mut csv).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            r"day,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8,total
+0,0,1,1,2,1,0,0,0,0,5
+1,1,1,2,1,0,0,0,0,0,5
+2,1,2,1,0,0,0,1,0,1,6
+"
+        );
+    }
+
+    #[test]
+    fn example_first_day_exceeding() {
+        let input = generator(INPUT);
+
+        assert_eq!(first_day_exceeding(&input, &PUZZLE, 5), Some(2));
+        assert_eq!(first_day_exceeding(&input, &PUZZLE, 25), Some(18));
+        assert_eq!(first_day_exceeding(&input, &PUZZLE, 26), Some(19));
+        assert_eq!(first_day_exceeding(&input, &PUZZLE, u128::MAX), None);
+        assert_eq!(first_day_exceeding(&[], &PUZZLE, 0), None);
+    }
+
+    #[test]
+    fn example_recurrence() {
+        let input = generator(INPUT);
+        let recurrence = derive_recurrence(&input, &PUZZLE);
+
+        // population(n) = population(n - 7) + population(n - 9)
+        assert_eq!(recurrence.coefficients, vec![0, 0, 0, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(
+            recurrence.characteristic_polynomial(),
+            vec![1, 0, 0, 0, 0, 0, 0, -1, 0, -1]
+        );
+        assert!((recurrence.growth_rate() - 1.0910244704807557).abs() < 1e-9);
+        assert!(recurrence.verify(&input, &PUZZLE, 256));
+
+        // right up to the last day the population fits in a u128, but not past it
+        let last = history(&input, &PUZZLE).count() - 1;
+        assert!(recurrence.verify(&input, &PUZZLE, last));
+        assert!(!recurrence.verify(&input, &PUZZLE, last + 1));
+
+        let totals: Vec<u128> = history(&input, &PUZZLE)
+            .map(|counts| counts.iter().sum())
+            .collect();
+        let start = totals.len() - recurrence.order();
+        assert_eq!(
+            recurrence.extend(&totals[start..], recurrence.order()),
+            Some(totals[start..].to_vec())
+        );
+        assert_eq!(
+            recurrence.extend(&totals[start..], recurrence.order() + 5),
+            None
+        );
+        assert_eq!(
+            Recurrence {
+                coefficients: vec![1, -2]
+            }
+            .extend(&[1, 1], 3),
+            None
+        );
+    }
+
+    #[test]
+    fn custom_recurrence() {
+        let input = generator(INPUT);
+        let lifecycles = [
+            Lifecycle {
+                reset: 1,
+                newborn: 1,
+                maturation_delay: 0,
+            },
+            Lifecycle {
+                reset: 9,
+                newborn: 4,
+                maturation_delay: 3,
+            },
+            Lifecycle {
+                reset: 3,
+                newborn: 3,
+                maturation_delay: 0,
+            },
+        ];
+
+        for lifecycle in lifecycles.iter() {
+            let recurrence = derive_recurrence(&input, lifecycle);
+            assert!(recurrence.verify(&input, lifecycle, 200));
+
+            // over a few weeks, since the population doesn't have to grow smoothly
+            let totals: Vec<u128> = history(&input, lifecycle)
+                .take(201)
+                .map(|counts| counts.iter().sum())
+                .collect();
+            let ratio = (totals[200] as f64 / totals[180] as f64).powf(0.05);
+            assert!((recurrence.growth_rate() - ratio).abs() < 1e-3);
+        }
+
+        // a single fish splitting every other day doubles every two days
+        let recurrence = derive_recurrence(&[LanternFish { days: 1 }], &lifecycles[0]);
+        assert_eq!(recurrence.coefficients, vec![0, 2]);
+        assert!((recurrence.growth_rate() - 2f64.sqrt()).abs() < 1e-9);
+    }
 }
@@ -1,5 +1,7 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use crate::rng::Rng;
+
 fn fuel_needed_constant(x: usize, y: usize) -> usize {
     if x > y {
         x - y
@@ -14,6 +16,501 @@ fn fuel_needed_linear(x: usize, y: usize) -> usize {
     (diff * (diff + 1)) / 2
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: usize,
+    pub fuel: u128,
+}
+
+fn distance(x: usize, y: usize) -> u128 {
+    x.abs_diff(y) as u128
+}
+
+fn triangle(x: usize, y: usize) -> u128 {
+    let d = distance(x, y);
+    d * (d + 1) / 2
+}
+
+fn total_fuel(crabs: &[usize], position: usize, cost: fn(usize, usize) -> u128) -> u128 {
+    crabs.iter().map(|crab| cost(position, *crab)).sum()
+}
+
+// With a constant cost per step, moving the meeting point towards the side with more crabs always
+// helps, so any median is optimal.
+pub fn align_median(crabs: &[usize]) -> Alignment {
+    let mut sorted = crabs.to_vec();
+    sorted.sort_unstable();
+    let position = sorted[(sorted.len() - 1) / 2];
+
+    Alignment {
+        position,
+        fuel: total_fuel(crabs, position, distance),
+    }
+}
+
+// The triangular cost is (d^2 + |d|) / 2, whose sum is minimised within half a step of the mean,
+// so only the positions around it need checking.
+pub fn align_mean(crabs: &[usize]) -> Alignment {
+    let sum: u128 = crabs.iter().map(|crab| *crab as u128).sum();
+    let mean = (sum / crabs.len() as u128) as usize;
+    let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+
+    (mean.saturating_sub(1)..=mean + 1)
+        .map(|position| position.clamp(min, max))
+        .map(|position| Alignment {
+            position,
+            fuel: total_fuel(crabs, position, triangle),
+        })
+        .min_by_key(|alignment| (alignment.fuel, alignment.position))
+        .unwrap()
+}
+
+pub trait FuelCost {
+    fn cost(&self, distance: usize) -> u128;
+
+    // Declares the cost convex and non-decreasing in the distance. The total fuel is then convex
+    // in the meeting point, which lets the optimizer binary search for it.
+    fn is_convex(&self) -> bool {
+        false
+    }
+}
+
+pub struct Constant;
+
+impl FuelCost for Constant {
+    fn cost(&self, distance: usize) -> u128 {
+        distance as u128
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+pub struct Triangular;
+
+impl FuelCost for Triangular {
+    fn cost(&self, distance: usize) -> u128 {
+        let d = distance as u128;
+        d * (d + 1) / 2
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+// any function of the distance, with no promises made about it
+impl<F: Fn(usize) -> u128> FuelCost for F {
+    fn cost(&self, distance: usize) -> u128 {
+        self(distance)
+    }
+}
+
+// a function of the distance that the caller vouches is convex and non-decreasing
+pub struct Convex<F>(pub F);
+
+impl<F: Fn(usize) -> u128> FuelCost for Convex<F> {
+    fn cost(&self, distance: usize) -> u128 {
+        (self.0)(distance)
+    }
+
+    fn is_convex(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub position: usize,
+    pub fuel: u128,
+    // fuel spent by each crab, weight included
+    pub per_crab: Vec<u128>,
+}
+
+// The cheapest meeting point between the outermost crabs, the leftmost one if there's a tie.
+// `weights` scale the fuel of each crab, every crab counts once if there are none.
+pub fn optimize<C: FuelCost>(crabs: &[usize], weights: Option<&[u128]>, cost: &C) -> Plan {
+    if let Some(weights) = weights {
+        assert_eq!(weights.len(), crabs.len(), "every crab needs a weight");
+    }
+
+    let weight = |i: usize| weights.map_or(1, |weights| weights[i]);
+    let fuel = |position: usize| -> u128 {
+        crabs
+            .iter()
+            .enumerate()
+            .map(|(i, crab)| weight(i) * cost.cost(position.abs_diff(*crab)))
+            .sum()
+    };
+    let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+
+    let position = if cost.is_convex() {
+        // the first position from which the fuel stops going down
+        let (mut low, mut high) = (min, max);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if fuel(mid) <= fuel(mid + 1) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        low
+    } else {
+        (min..=max).min_by_key(|position| fuel(*position)).unwrap()
+    };
+
+    let per_crab: Vec<u128> = crabs
+        .iter()
+        .enumerate()
+        .map(|(i, crab)| weight(i) * cost.cost(position.abs_diff(*crab)))
+        .collect();
+
+    Plan {
+        position,
+        fuel: per_crab.iter().sum(),
+        per_crab,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    pub fn distance(&self, a: &[usize], b: &[usize]) -> usize {
+        let steps = a.iter().zip(b).map(|(x, y)| x.abs_diff(*y));
+        match self {
+            Metric::Manhattan => steps.sum(),
+            Metric::Chebyshev => steps.max().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Meeting {
+    pub position: Vec<usize>,
+    pub fuel: u128,
+}
+
+// Fixes one axis at a time, taking the best meeting point over the remaining axes for each value.
+// Both metrics are convex, so with a convex cost the total fuel is too and every axis can be
+// binary searched. Otherwise the whole bounding box gets tried.
+fn search<F: Fn(&[usize]) -> u128>(
+    prefix: &mut Vec<usize>,
+    bounds: &[(usize, usize)],
+    convex: bool,
+    fuel: &F,
+) -> Meeting {
+    let axis = prefix.len();
+    if axis == bounds.len() {
+        return Meeting {
+            position: prefix.clone(),
+            fuel: fuel(prefix),
+        };
+    }
+
+    let mut best_at = |value: usize| {
+        prefix.push(value);
+        let meeting = search(prefix, bounds, convex, fuel);
+        prefix.pop();
+
+        meeting
+    };
+
+    let (min, max) = bounds[axis];
+    if convex {
+        let (mut low, mut high) = (min, max);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if best_at(mid).fuel <= best_at(mid + 1).fuel {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        best_at(low)
+    } else {
+        (min..=max)
+            .map(best_at)
+            .min_by_key(|meeting| meeting.fuel)
+            .unwrap()
+    }
+}
+
+pub fn align_nd<C: FuelCost>(crabs: &[Vec<usize>], metric: Metric, cost: &C) -> Meeting {
+    let dimensions = crabs[0].len();
+    assert!(
+        crabs.iter().all(|crab| crab.len() == dimensions),
+        "every crab needs {} coordinates",
+        dimensions
+    );
+
+    let bounds: Vec<(usize, usize)> = (0..dimensions)
+        .map(|axis| {
+            let values = crabs.iter().map(|crab| crab[axis]);
+            (values.clone().min().unwrap(), values.max().unwrap())
+        })
+        .collect();
+    let fuel = |position: &[usize]| -> u128 {
+        crabs
+            .iter()
+            .map(|crab| cost.cost(metric.distance(position, crab)))
+            .sum()
+    };
+
+    search(&mut Vec::new(), &bounds, cost.is_convex(), &fuel)
+}
+
+// Coordinates are separated by commas or whitespace and every `dimensions` of them make a crab, so
+// the puzzle input is the one dimensional case.
+pub fn generator_nd(input: &str, dimensions: usize) -> Vec<Vec<usize>> {
+    let coordinates: Vec<usize> = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|coordinate| !coordinate.is_empty())
+        .map(|coordinate| coordinate.parse().unwrap())
+        .collect();
+    assert_eq!(
+        coordinates.len() % dimensions,
+        0,
+        "coordinates don't split into crabs of {} dimensions",
+        dimensions
+    );
+
+    coordinates
+        .chunks(dimensions)
+        .map(|crab| crab.to_vec())
+        .collect()
+}
+
+// A treap keyed by position, each node standing for every crab at that position. The subtree
+// counts make it an order statistics tree and the subtree sums give prefix sums over positions.
+#[derive(Debug)]
+struct Node {
+    position: usize,
+    crabs: usize,
+    priority: u64,
+    left: Option<Box<Node>>,
+    right: Option<Box<Node>>,
+    count: usize,
+    sum: u128,
+}
+
+impl Node {
+    fn new(position: usize, priority: u64) -> Self {
+        Self {
+            position,
+            crabs: 0,
+            priority,
+            left: None,
+            right: None,
+            count: 0,
+            sum: 0,
+        }
+    }
+
+    fn update(&mut self) {
+        let (left_count, left_sum) = totals(&self.left);
+        let (right_count, right_sum) = totals(&self.right);
+        self.count = left_count + self.crabs + right_count;
+        self.sum = left_sum + self.crabs as u128 * self.position as u128 + right_sum;
+    }
+}
+
+fn totals(node: &Option<Box<Node>>) -> (usize, u128) {
+    node.as_ref().map_or((0, 0), |node| (node.count, node.sum))
+}
+
+// everything for which `goes_left` holds ends up on the left, it has to be monotone in the position
+fn split(
+    node: Option<Box<Node>>,
+    goes_left: &impl Fn(usize) -> bool,
+) -> (Option<Box<Node>>, Option<Box<Node>>) {
+    match node {
+        None => (None, None),
+        Some(mut node) => {
+            if goes_left(node.position) {
+                let (left, right) = split(node.right.take(), goes_left);
+                node.right = left;
+                node.update();
+                (Some(node), right)
+            } else {
+                let (left, right) = split(node.left.take(), goes_left);
+                node.left = right;
+                node.update();
+                (left, Some(node))
+            }
+        }
+    }
+}
+
+fn merge(left: Option<Box<Node>>, right: Option<Box<Node>>) -> Option<Box<Node>> {
+    match (left, right) {
+        (None, node) | (node, None) => node,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.update();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.update();
+                Some(right)
+            }
+        }
+    }
+}
+
+// Keeps the best meeting points up to date as crabs come and go, every operation is O(log n)
+// expected.
+#[derive(Debug)]
+pub struct Swarm {
+    root: Option<Box<Node>>,
+    squares: u128,
+    rng: Rng,
+}
+
+impl Swarm {
+    pub fn new(crabs: &[usize]) -> Self {
+        let mut swarm = Self {
+            root: None,
+            squares: 0,
+            rng: Rng::new(0x5eed),
+        };
+        crabs.iter().for_each(|crab| swarm.insert(*crab));
+
+        swarm
+    }
+
+    pub fn len(&self) -> usize {
+        totals(&self.root).0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Pulls the node at `position` out of the treap, hands it to `change` and puts it back unless
+    // no crabs are left there. Returns whatever `change` does.
+    fn modify(&mut self, position: usize, change: impl FnOnce(&mut Node) -> bool) -> bool {
+        let (less, rest) = split(self.root.take(), &|p| p < position);
+        let (at, greater) = split(rest, &|p| p <= position);
+
+        let priority = self.rng.next_u64();
+        let mut at = at.unwrap_or_else(|| Box::new(Node::new(position, priority)));
+        let changed = change(&mut at);
+        at.update();
+        let at = if at.crabs > 0 { Some(at) } else { None };
+
+        self.root = merge(merge(less, at), greater);
+        changed
+    }
+
+    pub fn insert(&mut self, position: usize) {
+        self.modify(position, |node| {
+            node.crabs += 1;
+            true
+        });
+        self.squares += position as u128 * position as u128;
+    }
+
+    // returns false if there was no crab at `position`
+    pub fn remove(&mut self, position: usize) -> bool {
+        let removed = self.modify(position, |node| {
+            let present = node.crabs > 0;
+            node.crabs = node.crabs.saturating_sub(1);
+            present
+        });
+        if removed {
+            self.squares -= position as u128 * position as u128;
+        }
+
+        removed
+    }
+
+    // the position of the k-th crab from the left, counting from 0
+    pub fn nth(&self, mut k: usize) -> Option<usize> {
+        let mut node = self.root.as_ref();
+        while let Some(current) = node {
+            let (left_count, _) = totals(&current.left);
+            if k < left_count {
+                node = current.left.as_ref();
+            } else if k < left_count + current.crabs {
+                return Some(current.position);
+            } else {
+                k -= left_count + current.crabs;
+                node = current.right.as_ref();
+            }
+        }
+
+        None
+    }
+
+    // how many crabs sit at or before `position` and the sum of their positions
+    fn prefix(&self, position: usize) -> (usize, u128) {
+        let (mut count, mut sum) = (0, 0);
+        let mut node = self.root.as_ref();
+        while let Some(current) = node {
+            if current.position <= position {
+                let (left_count, left_sum) = totals(&current.left);
+                count += left_count + current.crabs;
+                sum += left_sum + current.crabs as u128 * current.position as u128;
+                node = current.right.as_ref();
+            } else {
+                node = current.left.as_ref();
+            }
+        }
+
+        (count, sum)
+    }
+
+    // sum of the distances from every crab to `position`
+    pub fn distance_to(&self, position: usize) -> u128 {
+        let (count, sum) = totals(&self.root);
+        let (before, before_sum) = self.prefix(position);
+        let p = position as u128;
+
+        (p * before as u128 - before_sum) + ((sum - before_sum) - p * (count - before) as u128)
+    }
+
+    // sum of the triangular costs, (d^2 + d) / 2 summed up using the tracked sum of squares
+    pub fn triangle_to(&self, position: usize) -> u128 {
+        let (count, sum) = totals(&self.root);
+        let p = position as u128;
+        let squared = self.squares + count as u128 * p * p - 2 * p * sum;
+
+        (squared + self.distance_to(position)) / 2
+    }
+
+    pub fn best_median(&self) -> Option<Alignment> {
+        let position = self.nth(self.len().checked_sub(1)? / 2)?;
+
+        Some(Alignment {
+            position,
+            fuel: self.distance_to(position),
+        })
+    }
+
+    pub fn best_mean(&self) -> Option<Alignment> {
+        let (count, sum) = totals(&self.root);
+        let mean = (sum / count.max(1) as u128) as usize;
+        let (min, max) = (self.nth(0)?, self.nth(count - 1)?);
+
+        (mean.saturating_sub(1)..=mean + 1)
+            .map(|position| position.clamp(min, max))
+            .map(|position| Alignment {
+                position,
+                fuel: self.triangle_to(position),
+            })
+            .min_by_key(|alignment| (alignment.fuel, alignment.position))
+    }
+}
+
 #[aoc_generator(day7)]
 pub fn generator(input: &str) -> Vec<usize> {
     input
@@ -55,6 +552,16 @@ pub fn solver_2(crabs: &Vec<usize>) -> usize {
         .unwrap()
 }
 
+#[aoc(day7, part1, Median)]
+pub fn solver_1_median(crabs: &[usize]) -> u128 {
+    align_median(crabs).fuel
+}
+
+#[aoc(day7, part2, Mean)]
+pub fn solver_2_mean(crabs: &[usize]) -> u128 {
+    align_mean(crabs).fuel
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,4 +583,180 @@ mod tests {
 
         assert_eq!(result, 168);
     }
+
+    #[test]
+    fn test_closed_form() {
+        let crabs = generator(INPUT);
+
+        assert_eq!(
+            align_median(&crabs),
+            Alignment {
+                position: 2,
+                fuel: 37
+            }
+        );
+        assert_eq!(
+            align_mean(&crabs),
+            Alignment {
+                position: 5,
+                fuel: 168
+            }
+        );
+    }
+
+    #[test]
+    fn test_closed_form_matches_brute_force() {
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let crabs: Vec<usize> = (0..1 + rng.below(20)).map(|_| rng.below(50)).collect();
+
+            assert_eq!(solver_1_median(&crabs), solver_1(&crabs) as u128);
+            assert_eq!(solver_2_mean(&crabs), solver_2(&crabs) as u128);
+        }
+    }
+
+    #[test]
+    fn test_closed_form_huge() {
+        let crabs = vec![0, 1_000_000_000, 999_999_999, 3, 1_000_000_000];
+
+        assert_eq!(align_median(&crabs).position, 999_999_999);
+        assert_eq!(align_mean(&crabs).position, 600_000_000);
+    }
+
+    #[test]
+    fn test_optimize() {
+        let crabs = generator(INPUT);
+
+        let plan = optimize(&crabs, None, &Constant);
+        assert_eq!((plan.position, plan.fuel), (2, 37));
+        assert_eq!(plan.per_crab, vec![14, 1, 0, 2, 2, 0, 5, 1, 0, 12]);
+
+        let plan = optimize(&crabs, None, &Triangular);
+        assert_eq!((plan.position, plan.fuel), (5, 168));
+        assert_eq!(plan.per_crab[0], 66);
+
+        // one heavy crab drags everyone else to it
+        let mut weights = vec![1; crabs.len()];
+        weights[0] = 100;
+        assert_eq!(optimize(&crabs, Some(&weights), &Constant).position, 16);
+    }
+
+    #[test]
+    fn test_optimize_custom_costs() {
+        let mut rng = Rng::new(45);
+        for _ in 0..50 {
+            let crabs: Vec<usize> = (0..1 + rng.below(15)).map(|_| rng.below(40)).collect();
+            let weights: Vec<u128> = crabs.iter().map(|_| rng.below(5) as u128).collect();
+
+            // the binary search has to agree with trying everything
+            let cubic = |d: usize| (d * d * d) as u128;
+            assert_eq!(
+                optimize(&crabs, Some(&weights), &Convex(cubic)),
+                optimize(&crabs, Some(&weights), &cubic)
+            );
+            assert_eq!(
+                optimize(&crabs, None, &Convex(|d: usize| d as u128)),
+                optimize(&crabs, None, &Constant)
+            );
+        }
+
+        // free up to 3 steps, expensive after that: not convex, so every position gets tried
+        let crabs = vec![0, 6, 12];
+        let cliff = |d: usize| if d <= 3 { 0 } else { 100 };
+        let plan = optimize(&crabs, None, &cliff);
+        assert_eq!((plan.position, plan.fuel), (3, 100));
+        let crabs = vec![0, 3, 6];
+        assert_eq!(optimize(&crabs, None, &cliff).position, 3);
+        assert_eq!(optimize(&crabs, None, &cliff).fuel, 0);
+    }
+
+    #[test]
+    fn test_align_nd_one_dimension() {
+        let crabs = generator_nd(INPUT, 1);
+        assert_eq!(crabs.concat(), generator(INPUT));
+
+        for metric in [Metric::Manhattan, Metric::Chebyshev] {
+            assert_eq!(align_nd(&crabs, metric, &Constant).fuel, 37);
+            assert_eq!(align_nd(&crabs, metric, &Triangular).fuel, 168);
+        }
+    }
+
+    #[test]
+    fn test_align_nd() {
+        let crabs = generator_nd("0,0\n4,0\n0,4\n4,4\n2,2", 2);
+        assert_eq!(
+            align_nd(&crabs, Metric::Manhattan, &Constant),
+            Meeting {
+                position: vec![2, 2],
+                fuel: 16
+            }
+        );
+        assert_eq!(align_nd(&crabs, Metric::Chebyshev, &Constant).fuel, 8);
+
+        // a plain closure isn't known to be convex, so every point is tried
+        let mut rng = Rng::new(46);
+        for _ in 0..30 {
+            let dimensions = 2 + rng.below(2);
+            let crabs: Vec<Vec<usize>> = (0..1 + rng.below(8))
+                .map(|_| (0..dimensions).map(|_| rng.below(12)).collect())
+                .collect();
+
+            for metric in [Metric::Manhattan, Metric::Chebyshev] {
+                let exhaustive = |d: usize| d as u128;
+                assert_eq!(
+                    align_nd(&crabs, metric, &Constant).fuel,
+                    align_nd(&crabs, metric, &exhaustive).fuel
+                );
+                let exhaustive = |d: usize| Triangular.cost(d);
+                assert_eq!(
+                    align_nd(&crabs, metric, &Triangular).fuel,
+                    align_nd(&crabs, metric, &exhaustive).fuel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_swarm() {
+        let crabs = generator(INPUT);
+        let mut swarm = Swarm::new(&crabs);
+
+        assert_eq!(swarm.len(), 10);
+        assert_eq!(swarm.best_median(), Some(align_median(&crabs)));
+        assert_eq!(swarm.best_mean(), Some(align_mean(&crabs)));
+        assert_eq!(swarm.nth(9), Some(16));
+        assert_eq!(swarm.nth(10), None);
+
+        assert!(swarm.remove(16));
+        assert!(!swarm.remove(3));
+        assert_eq!(swarm.best_median().unwrap().fuel, 37 - 14);
+
+        (0..9).for_each(|_| {
+            swarm.remove(swarm.nth(0).unwrap());
+        });
+        assert!(swarm.is_empty());
+        assert_eq!(swarm.best_median(), None);
+        assert_eq!(swarm.best_mean(), None);
+    }
+
+    #[test]
+    fn test_swarm_random_updates() {
+        let mut rng = Rng::new(47);
+        let mut crabs: Vec<usize> = (0..20).map(|_| rng.below(100)).collect();
+        let mut swarm = Swarm::new(&crabs);
+
+        for _ in 0..500 {
+            if crabs.len() > 1 && rng.below(2) == 0 {
+                let crab = crabs.swap_remove(rng.below(crabs.len()));
+                assert!(swarm.remove(crab));
+            } else {
+                let crab = rng.below(100);
+                crabs.push(crab);
+                swarm.insert(crab);
+            }
+
+            assert_eq!(swarm.best_median(), Some(align_median(&crabs)));
+            assert_eq!(swarm.best_mean(), Some(align_mean(&crabs)));
+        }
+    }
 }
@@ -1,8 +1,10 @@
-use std::collections::HashSet;
-
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+
+use crate::rng::Rng;
 
-type Digit = HashSet<char>;
+// one bit per segment, bit 0 is wire 'a' and bit 6 is wire 'g'
+type Digit = u8;
 
 #[derive(Debug, Clone)]
 pub struct Entry {
@@ -10,6 +12,16 @@ pub struct Entry {
     output: [Digit; 4],
 }
 
+fn parse_digit(pattern: &str) -> Digit {
+    pattern
+        .bytes()
+        .fold(0, |digit, wire| digit | 1 << (wire - b'a'))
+}
+
+fn segments(digit: Digit) -> u32 {
+    digit.count_ones()
+}
+
 #[aoc_generator(day8)]
 pub fn generator(input: &str) -> Vec<Entry> {
     input
@@ -21,7 +33,7 @@ pub fn generator(input: &str) -> Vec<Entry> {
             let signals: [Digit; 10] = entry[0]
                 .trim()
                 .splitn(10, ' ')
-                .map(|s| s.to_string().chars().collect())
+                .map(parse_digit)
                 .collect::<Vec<Digit>>()
                 .try_into()
                 .unwrap();
@@ -29,7 +41,7 @@ pub fn generator(input: &str) -> Vec<Entry> {
             let output: [Digit; 4] = entry[1]
                 .trim()
                 .splitn(10, ' ')
-                .map(|s| s.to_string().chars().collect())
+                .map(parse_digit)
                 .collect::<Vec<Digit>>()
                 .try_into()
                 .unwrap();
@@ -42,7 +54,7 @@ pub fn generator(input: &str) -> Vec<Entry> {
 #[aoc(day8, part1)]
 pub fn solver_1(entries: &Vec<Entry>) -> usize {
     let check_digit = |digit: &&Digit| {
-        let len = digit.len();
+        let len = segments(**digit);
         // 1 -> 2, 4 -> 4, 7 -> 3, 8 -> 8
         len == 2 || len == 4 || len == 3 || len == 7
     };
@@ -60,12 +72,12 @@ macro_rules! find_and_remove {
     }};
 }
 
-fn digit_diff(a: &Digit, b: &Digit) -> Digit {
-    a.difference(&b).cloned().collect()
+fn digit_diff(a: Digit, b: Digit) -> Digit {
+    a & !b
 }
 
-fn digit_union(a: &Digit, b: &Digit) -> Digit {
-    a.union(&b).cloned().collect()
+fn digit_union(a: Digit, b: Digit) -> Digit {
+    a | b
 }
 
 fn resolve_signals(signals: &[Digit; 10]) -> [Digit; 10] {
@@ -83,46 +95,258 @@ fn resolve_signals(signals: &[Digit; 10]) -> [Digit; 10] {
     // 4: remaining with 1
     // 5: remaining
 
-    let mut signals: Vec<Digit> = signals.iter().cloned().collect();
+    let mut signals: Vec<Digit> = signals.to_vec();
 
-    let one = find_and_remove!(signals, |d| d.len() == 2);
-    let four = find_and_remove!(signals, |d| d.len() == 4);
-    let seven = find_and_remove!(signals, |d| d.len() == 3);
-    let eight = find_and_remove!(signals, |d| d.len() == 7);
+    let one = find_and_remove!(signals, |d| segments(*d) == 2);
+    let four = find_and_remove!(signals, |d| segments(*d) == 4);
+    let seven = find_and_remove!(signals, |d| segments(*d) == 3);
+    let eight = find_and_remove!(signals, |d| segments(*d) == 7);
 
-    let bottom_l = digit_diff(&eight, &digit_union(&four, &seven));
-    let e = digit_diff(&eight, &one);
+    let bottom_l = digit_diff(eight, digit_union(four, seven));
+    let e = digit_diff(eight, one);
 
-    let nine = find_and_remove!(signals, |d| digit_union(&bottom_l, &digit_diff(&eight, &d))
-        .len()
-        == 2);
-    let bottom_left_edge = digit_diff(&eight, &nine).into_iter().next().unwrap();
-    let six = find_and_remove!(signals, |d| e.is_subset(&d));
-    let zero = find_and_remove!(signals, |d| d.len() == 6);
-    let two = find_and_remove!(signals, |d| d.contains(&bottom_left_edge));
-    let five = find_and_remove!(signals, |d| digit_diff(&d, &one).len() == 4);
-    let three = signals.into_iter().next().unwrap();
+    let nine = find_and_remove!(signals, |d| segments(digit_union(
+        bottom_l,
+        digit_diff(eight, *d)
+    )) == 2);
+    let bottom_left_edge = digit_diff(eight, nine);
+    let six = find_and_remove!(signals, |d| *d & e == e);
+    let zero = find_and_remove!(signals, |d| segments(*d) == 6);
+    let two = find_and_remove!(signals, |d| *d & bottom_left_edge != 0);
+    let five = find_and_remove!(signals, |d| segments(digit_diff(*d, one)) == 4);
+    let three = signals[0];
 
     [zero, one, two, three, four, five, six, seven, eight, nine]
 }
 
+// maps every one of the 128 segment patterns straight to the digit it shows, if any
+fn decoder(resolved: &[Digit; 10]) -> [Option<usize>; 128] {
+    let mut table = [None; 128];
+    resolved
+        .iter()
+        .enumerate()
+        .for_each(|(value, digit)| table[*digit as usize] = Some(value));
+
+    table
+}
+
 #[aoc(day8, part2)]
 pub fn solver_2(entries: &Vec<Entry>) -> usize {
     entries
-        .into_iter()
+        .iter()
         .map(|entry| {
-            let resolved = resolve_signals(&entry.signals);
-            let output: Vec<usize> = entry
+            let table = decoder(&resolve_signals(&entry.signals));
+
+            entry
                 .output
                 .iter()
-                .map(|o| resolved.iter().position(|r| r.eq(&o)).unwrap())
+                .fold(0, |number, o| number * 10 + table[*o as usize].unwrap())
+        })
+        .sum()
+}
+
+// the segments lit for each digit on a correctly wired display
+const DIGITS: [Digit; 10] = [
+    0b111_0111, // abcefg
+    0b010_0100, // cf
+    0b101_1101, // acdeg
+    0b110_1101, // acdfg
+    0b010_1110, // bcdf
+    0b110_1011, // abdfg
+    0b111_1011, // abdefg
+    0b010_0101, // acf
+    0b111_1111, // abcdefg
+    0b110_1111, // abcdfg
+];
+
+// `wiring[segment]` is the wire that lights up that segment
+pub type Wiring = [u8; 7];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solution {
+    Unique(Wiring),
+    // only possible with some signal patterns missing, the ten digits tell every wire apart
+    Ambiguous(Vec<Wiring>),
+    // the patterns left broken by every one of the closest wirings
+    Inconsistent(Vec<String>),
+}
+
+fn format_digit(digit: Digit) -> String {
+    (0..7)
+        .filter(|wire| digit & 1 << wire != 0)
+        .map(|wire| (b'a' + wire) as char)
+        .collect()
+}
+
+// the segments a pattern lights up, looking only at the first `known` segments of the wiring
+fn translate(pattern: Digit, wiring: &Wiring, known: usize) -> Digit {
+    (0..known)
+        .filter(|segment| pattern & 1 << wiring[*segment] != 0)
+        .fold(0, |digit, segment| digit | 1 << segment)
+}
+
+fn fits(pattern: Digit, wiring: &Wiring, known: usize) -> bool {
+    let mask = (1 << known) - 1;
+    let segments = translate(pattern, wiring, known);
+
+    DIGITS
+        .iter()
+        .any(|digit| digit.count_ones() == pattern.count_ones() && digit & mask == segments)
+}
+
+// The patterns a wiring gets wrong: those that show no digit at all, and signal patterns showing a
+// digit some other signal pattern shows too. With neither, ten signals are exactly the ten digits.
+fn broken(signals: &[Digit], output: &[Digit], wiring: &Wiring) -> Vec<Digit> {
+    let shown: Vec<Option<usize>> = signals
+        .iter()
+        .map(|pattern| decode(*pattern, wiring))
+        .collect();
+    let repeated = signals
+        .iter()
+        .zip(&shown)
+        .filter(|(_, digit)| digit.is_some() && shown.iter().filter(|d| d == digit).count() > 1)
+        .map(|(pattern, _)| *pattern);
+    let unreadable = signals
+        .iter()
+        .chain(output)
+        .filter(|pattern| decode(**pattern, wiring).is_none())
+        .copied();
+
+    unreadable.chain(repeated).collect()
+}
+
+fn search(
+    signals: &[Digit],
+    output: &[Digit],
+    wiring: &mut Wiring,
+    known: usize,
+    found: &mut Vec<Wiring>,
+) {
+    if known == 7 {
+        if broken(signals, output, wiring).is_empty() {
+            found.push(*wiring);
+        }
+        return;
+    }
+
+    for wire in 0..7 {
+        if wiring[..known].contains(&wire) {
+            continue;
+        }
+
+        wiring[known] = wire;
+        // a wire choice that leaves some pattern matching no digit can't lead anywhere
+        if signals
+            .iter()
+            .chain(output)
+            .all(|pattern| fits(*pattern, wiring, known + 1))
+        {
+            search(signals, output, wiring, known + 1, found);
+        }
+    }
+}
+
+fn solve(signals: &[Digit], output: &[Digit]) -> Solution {
+    let mut found = Vec::new();
+    search(signals, output, &mut [0; 7], 0, &mut found);
+
+    match found.len() {
+        1 => Solution::Unique(found[0]),
+        0 => {
+            // blame whatever is left broken by the wirings that get the most patterns right
+            let wirings: Vec<Vec<Digit>> = (0..7u8)
+                .permutations(7)
+                .map(|wiring| broken(signals, output, &wiring.try_into().unwrap()))
+                .collect();
+            let fewest = wirings.iter().map(|broken| broken.len()).min().unwrap();
+
+            let mut offending: Vec<Digit> = wirings
+                .into_iter()
+                .filter(|broken| broken.len() == fewest)
+                .flatten()
                 .collect();
+            offending.sort_unstable();
+            offending.dedup();
+
+            Solution::Inconsistent(offending.into_iter().map(format_digit).collect())
+        }
+        _ => Solution::Ambiguous(found),
+    }
+}
+
+// Finds every wiring under which the signal patterns show the ten digits once each and every
+// output pattern shows one of them.
+pub fn solve_wiring(entry: &Entry) -> Solution {
+    solve(&entry.signals, &entry.output)
+}
+
+// Like `solve_wiring`, for a display where only some of the signal patterns were seen. They still
+// have to show different digits, but with fewer than ten of them the wiring may not be pinned down.
+pub fn solve_patterns(signals: &[&str], output: &[&str]) -> Solution {
+    let signals: Vec<Digit> = signals.iter().map(|pattern| parse_digit(pattern)).collect();
+    let output: Vec<Digit> = output.iter().map(|pattern| parse_digit(pattern)).collect();
 
-            output[0] * 1000 + output[1] * 100 + output[2] * 10 + output[3]
+    solve(&signals, &output)
+}
+
+pub fn decode(pattern: Digit, wiring: &Wiring) -> Option<usize> {
+    let segments = translate(pattern, wiring, 7);
+    DIGITS.iter().position(|digit| *digit == segments)
+}
+
+#[aoc(day8, part2, Permutations)]
+pub fn solver_2_permutations(entries: &[Entry]) -> usize {
+    entries
+        .iter()
+        .map(|entry| match solve_wiring(entry) {
+            Solution::Unique(wiring) => entry
+                .output
+                .iter()
+                .fold(0, |number, o| number * 10 + decode(*o, &wiring).unwrap()),
+            solution => panic!("no single wiring for {:?}: {:?}", entry, solution),
         })
         .sum()
 }
 
+// The puzzle line for a display wired up as `wiring` showing `number`, with the signal patterns and
+// the letters within every pattern shuffled.
+pub fn encode_line(wiring: &Wiring, number: usize, seed: u64) -> String {
+    assert!(
+        number < 10000,
+        "the display only has 4 digits, got {}",
+        number
+    );
+    let mut sorted = *wiring;
+    sorted.sort_unstable();
+    assert_eq!(sorted, [0, 1, 2, 3, 4, 5, 6], "not a wiring: {:?}", wiring);
+
+    let mut rng = Rng::new(seed);
+    let mut values: Vec<usize> = (0..10).collect();
+    rng.shuffle(&mut values);
+
+    let mut scramble = |value: usize| -> String {
+        let mut wires: Vec<char> = (0..7)
+            .filter(|segment| DIGITS[value] & 1 << segment != 0)
+            .map(|segment| (b'a' + wiring[segment]) as char)
+            .collect();
+        rng.shuffle(&mut wires);
+
+        wires.into_iter().collect()
+    };
+
+    let signals: Vec<String> = values.into_iter().map(&mut scramble).collect();
+    let output: Vec<String> = [1000, 100, 10, 1]
+        .iter()
+        .map(|place| scramble(number / place % 10))
+        .collect();
+
+    format!("{} | {}", signals.join(" "), output.join(" "))
+}
+
+pub fn encode(wiring: &Wiring, number: usize, seed: u64) -> Entry {
+    generator(&encode_line(wiring, number, seed)).remove(0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +377,129 @@ gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
 
         assert_eq!(result, 61229);
     }
+
+    #[test]
+    fn test_resolve_signals() {
+        let entries = generator(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        );
+        let resolved = resolve_signals(&entries[0].signals);
+
+        assert_eq!(resolved[5], parse_digit("cdfbe"));
+        assert_eq!(resolved[8], 0b111_1111);
+        assert_eq!(decoder(&resolved)[parse_digit("fbcad") as usize], Some(3));
+        assert_eq!(decoder(&resolved)[parse_digit("ag") as usize], None);
+        assert_eq!(solver_2(&entries), 5353);
+    }
+
+    #[test]
+    fn test_solve_wiring() {
+        let entries = generator(INPUT);
+        assert_eq!(solver_2_permutations(&entries), 61229);
+
+        let entry = &generator(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        )[0];
+        assert_eq!(solve_wiring(entry), Solution::Unique([3, 4, 0, 5, 6, 1, 2]));
+    }
+
+    #[test]
+    fn test_solve_wiring_repeated_signals() {
+        // ten 1s read fine one at a time, but they aren't ten different digits
+        let entry = &generator("ab ab ab ab ab ab ab ab ab ab | ab ba ab ba")[0];
+        assert_eq!(
+            solve_wiring(entry),
+            Solution::Inconsistent(vec!["ab".to_string()])
+        );
+
+        // the 5 `cdfbe` swapped for a second copy of the 3
+        let entry = &generator(
+            "acedgfb fbcad gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        )[0];
+        assert_eq!(
+            solve_wiring(entry),
+            Solution::Inconsistent(vec!["abcdf".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_solve_patterns() {
+        // only a 1 was seen, so its two wires can go either way and the rest anywhere
+        match solve_patterns(&["ab"], &["ab", "ba"]) {
+            Solution::Ambiguous(wirings) => {
+                assert_eq!(wirings.len(), 2 * 120);
+                assert!(wirings.iter().all(|wiring| decode(0b11, wiring) == Some(1)));
+            }
+            solution => panic!("expected an ambiguous entry, got {:?}", solution),
+        }
+
+        // the 1, 7, 4 and 8 pin down the top, but c/f, b/d and e/g can each go either way
+        match solve_patterns(&["ab", "dab", "eafb", "acedgfb"], &[]) {
+            Solution::Ambiguous(wirings) => assert_eq!(wirings.len(), 8),
+            solution => panic!("expected an ambiguous entry, got {:?}", solution),
+        }
+
+        let signals = [
+            "acedgfb", "cdfbe", "gcdfa", "fbcad", "dab", "cefabd", "cdfgeb", "eafb", "cagedb",
+        ];
+        assert_eq!(
+            solve_patterns(&signals, &["cdfeb"]),
+            Solution::Unique([3, 4, 0, 5, 6, 1, 2])
+        );
+        assert_eq!(
+            solve_patterns(&["ab", "ba"], &[]),
+            Solution::Inconsistent(vec!["ab".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_solve_wiring_inconsistent() {
+        // the 7 `dab` picked up an extra wire
+        let entry = &generator(
+            "acedgfb cdfbe gcdfa fbcad dabe cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        )[0];
+        assert_eq!(
+            solve_wiring(entry),
+            Solution::Inconsistent(vec!["abde".to_string()])
+        );
+
+        let entry = &generator(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | a fcadb cdfeb cdbaf",
+        )[0];
+        assert_eq!(
+            solve_wiring(entry),
+            Solution::Inconsistent(vec!["a".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let wiring = [3, 4, 0, 5, 6, 1, 2];
+        let line = encode_line(&wiring, 5353, 50);
+        assert_eq!(line, encode_line(&wiring, 5353, 50));
+        assert_eq!(solver_2(&generator(&line)), 5353);
+
+        // same patterns as the puzzle's example, just in some other order
+        let example = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab";
+        let mut expected: Vec<Digit> = example.split(' ').map(parse_digit).collect();
+        let mut signals = encode(&wiring, 5353, 50).signals.to_vec();
+        expected.sort_unstable();
+        signals.sort_unstable();
+        assert_eq!(signals, expected);
+    }
+
+    #[test]
+    fn test_encode_fuzz() {
+        let mut rng = Rng::new(50);
+        for seed in 0..200 {
+            let mut wiring: Wiring = [0, 1, 2, 3, 4, 5, 6];
+            rng.shuffle(&mut wiring);
+            let number = rng.below(10000);
+
+            let entries = vec![encode(&wiring, number, seed)];
+            assert_eq!(solver_2(&entries), number);
+            assert_eq!(solver_2_permutations(&entries), number);
+            assert_eq!(solve_wiring(&entries[0]), Solution::Unique(wiring));
+        }
+    }
 }